@@ -0,0 +1,79 @@
+"""Extracts planar polygons, such as roof faces, from connected lines in the three dimensional space."""
+
+import json
+
+import numpy as np
+
+from ._polygonalize import polygonalize
+
+__all__ = ["polygonalize", "polygonalize_geometries"]
+
+
+def polygonalize_geometries(geometries, groups=None, **tolerances):
+    """Extracts the polygons formed by Shapely `geometries` as `polygonalize` does.
+
+    Each line string, or each part of a multi line string, is split into its segments, which
+    must have z coordinates. When given, `groups` holds one label per geometry, such as the
+    building number, and is written back onto the polygons of each group as is.
+
+    Returns a list of pairs, each polygon as a Shapely polygon with z coordinates and its
+    attributes.
+    """
+    import shapely
+
+    geometries = list(geometries)
+    labels = None if groups is None else list(groups)
+
+    if labels is not None and len(labels) != len(geometries):
+        raise ValueError(f"{len(labels)} groups given for {len(geometries)} geometries")
+
+    lines = []
+    names = []
+    # labels as given, by the name of their group
+    originals = {}
+
+    for index, geometry in enumerate(geometries):
+        if not geometry.has_z:
+            raise ValueError(f"geometry {index} has no z coordinates")
+
+        parts = getattr(geometry, "geoms", [geometry])
+
+        for part in parts:
+            coordinates = shapely.get_coordinates(part, include_z=True)
+            segments = np.stack([coordinates[:-1], coordinates[1:]], axis=1)
+
+            lines.append(segments)
+
+            if labels is not None:
+                name = _group_name(labels[index])
+
+                originals.setdefault(name, labels[index])
+                names.extend([name] * len(segments))
+
+    lines = np.concatenate(lines) if lines else np.empty((0, 2, 3))
+    polygons = polygonalize(lines, None if labels is None else names, **tolerances)
+
+    for _, attributes in polygons:
+        if "group" in attributes:
+            attributes["group"] = originals[attributes["group"]]
+
+    return [(shapely.Polygon(ring), attributes) for ring, attributes in polygons]
+
+
+def _group_name(label):
+    """Name of the group of `label`, written as the geojson output names groups: strings as they
+    are unless they read as another JSON value, and other values as JSON, so that labels such as
+    `1` and `"1"` form different groups."""
+    if isinstance(label, str):
+        try:
+            json.loads(label)
+        except ValueError:
+            return label
+
+    if isinstance(label, np.generic):
+        label = label.item()
+
+    try:
+        return json.dumps(label)
+    except TypeError:
+        return repr(label)
@@ -0,0 +1,87 @@
+import numpy as np
+import pytest
+
+from polygonalize import polygonalize, polygonalize_geometries
+
+# gable roof
+GABLE = [
+    [[0.0, 0.0, 5.0], [10.0, 0.0, 5.0]],
+    [[0.0, 0.0, 5.0], [0.0, 4.0, 8.0]],
+    [[10.0, 0.0, 5.0], [10.0, 4.0, 8.0]],
+    [[0.0, 4.0, 8.0], [10.0, 4.0, 8.0]],
+    [[0.0, 4.0, 8.0], [0.0, 8.0, 5.0]],
+    [[10.0, 4.0, 8.0], [10.0, 8.0, 5.0]],
+    [[0.0, 8.0, 5.0], [10.0, 8.0, 5.0]],
+]
+
+
+def test_gable_roof_is_split_in_two_faces():
+    polygons = polygonalize(np.array(GABLE))
+
+    assert len(polygons) == 2
+
+    for ring, attributes in polygons:
+        assert ring.shape == (5, 3)
+        np.testing.assert_array_equal(ring[0], ring[-1])
+        assert attributes["surface"] == "RoofSurface"
+        assert attributes["area"] == pytest.approx(50.0)
+        assert attributes["normal"][2] > 0
+        assert "group" not in attributes
+
+
+def test_tolerances_are_passed_on():
+    # the ridge sags by 0.3 m in the middle of the roof
+    lines = np.array(GABLE)
+    lines[3] = [[0.0, 4.0, 8.0], [5.0, 4.0, 7.7]]
+    lines = np.concatenate([lines, [[[5.0, 4.0, 7.7], [10.0, 4.0, 8.0]]]])
+
+    assert len(polygonalize(lines, angle=0.25, distance=0.05, angle_sum=0.25)) == 0
+    assert len(polygonalize(lines, angle=15.0, distance=0.5, angle_sum=15.0)) == 2
+
+
+def test_lines_are_grouped():
+    lines = np.concatenate([GABLE, np.array(GABLE) + [100.0, 0.0, 0.0]])
+    groups = ["a"] * len(GABLE) + ["b"] * len(GABLE)
+    polygons = polygonalize(lines, groups)
+
+    assert [attributes["group"] for _, attributes in polygons] == ["a", "a", "b", "b"]
+
+
+def test_invalid_input_is_rejected():
+    with pytest.raises(ValueError):
+        polygonalize(np.zeros((4, 3, 3)))
+
+    with pytest.raises(ValueError):
+        polygonalize(np.array(GABLE), ["a"])
+
+
+def test_shapely_geometries():
+    shapely = pytest.importorskip("shapely")
+    # eaves and gables as line strings, the ridge as a single segment
+    geometries = [
+        shapely.LineString([(0, 0, 5), (10, 0, 5), (10, 4, 8), (10, 8, 5), (0, 8, 5)]),
+        shapely.LineString([(0, 0, 5), (0, 4, 8), (0, 8, 5)]),
+        shapely.LineString([(0, 4, 8), (10, 4, 8)]),
+    ]
+    polygons = polygonalize_geometries(geometries, groups=[7, 7, 7], angle=5.0)
+
+    assert len(polygons) == 2
+
+    for polygon, attributes in polygons:
+        assert isinstance(polygon, shapely.Polygon)
+        assert polygon.has_z
+        assert polygon.area > 0
+        assert attributes["group"] == 7
+
+    with pytest.raises(ValueError):
+        polygonalize_geometries([shapely.LineString([(0, 0), (1, 0)])])
+
+
+def test_labels_of_different_types_are_kept_apart():
+    shapely = pytest.importorskip("shapely")
+    geometries = [shapely.LineString(line) for line in GABLE] + [
+        shapely.LineString(np.array(line) + [100.0, 0.0, 0.0]) for line in GABLE
+    ]
+    polygons = polygonalize_geometries(geometries, groups=[1] * len(GABLE) + ["1"] * len(GABLE))
+
+    assert [attributes["group"] for _, attributes in polygons] == [1, 1, "1", "1"]
@@ -0,0 +1,153 @@
+use serde_json::Map;
+use serde_json::Value;
+
+/// Rules combining the values a property takes on the lines forming the boundary of a polygon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    /// Value of the first boundary line having the property.
+    First,
+    /// List of the distinct values, in order of appearance along the boundary.
+    Unique,
+    /// Smallest value, numbers being compared numerically and other values as text, which orders
+    /// ISO 8601 dates chronologically.
+    Min,
+    /// Largest value, compared as for [`Aggregation::Min`].
+    Max,
+    /// Most frequent value, ties being broken by order of appearance.
+    Majority,
+}
+
+/// Propagation of an input line property to the output polygons.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyRule {
+    /// Name of the property, both in the input lines and the output polygons.
+    pub property: String,
+    /// How the values of the boundary lines are combined.
+    pub aggregation: Aggregation,
+}
+
+impl Aggregation {
+    /// Combines `values`, yielding null when there is none.
+    pub fn apply(&self, values: &[&Value]) -> Value {
+        match self {
+            Aggregation::First => values.first().map_or(Value::Null, |value| (*value).clone()),
+            Aggregation::Unique => {
+                let mut unique = Vec::<Value>::new();
+
+                for value in values {
+                    if !unique.contains(value) {
+                        unique.push((*value).clone());
+                    }
+                }
+
+                Value::Array(unique)
+            }
+            Aggregation::Min => values
+                .iter()
+                .min_by(|a, b| Aggregation::compare(a, b))
+                .map_or(Value::Null, |value| (*value).clone()),
+            Aggregation::Max => values
+                .iter()
+                .rev()
+                .max_by(|a, b| Aggregation::compare(a, b))
+                .map_or(Value::Null, |value| (*value).clone()),
+            Aggregation::Majority => {
+                let mut counts = Vec::<(&Value, usize)>::new();
+
+                for value in values {
+                    match counts.iter_mut().find(|(other, _)| other == value) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((value, 1)),
+                    }
+                }
+
+                counts
+                    .iter()
+                    .rev()
+                    .max_by_key(|(_, count)| *count)
+                    .map_or(Value::Null, |(value, _)| (*value).clone())
+            }
+        }
+    }
+
+    fn compare(a: &Value, b: &Value) -> std::cmp::Ordering {
+        match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => Aggregation::text(a).cmp(&Aggregation::text(b)),
+        }
+    }
+
+    fn text(value: &Value) -> String {
+        match value.as_str() {
+            Some(text) => text.to_string(),
+            None => value.to_string(),
+        }
+    }
+}
+
+impl PropertyRule {
+    pub fn new(property: &str, aggregation: Aggregation) -> Self {
+        Self {
+            property: property.to_string(),
+            aggregation,
+        }
+    }
+
+    /// Applies each rule in `rules` to the `properties` of the boundary lines of a polygon,
+    /// ignoring lines lacking a property or having it null.
+    pub fn aggregate(
+        rules: &[PropertyRule],
+        properties: &[&Map<String, Value>],
+    ) -> Map<String, Value> {
+        rules
+            .iter()
+            .map(|rule| {
+                let values = properties
+                    .iter()
+                    .filter_map(|properties| properties.get(&rule.property))
+                    .filter(|value| !value.is_null())
+                    .collect::<Vec<_>>();
+
+                (rule.property.clone(), rule.aggregation.apply(&values))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn aggregation_rules() {
+        let values = [
+            json!("2021-05-04"),
+            json!("2019-11-30"),
+            json!("2021-05-04"),
+            json!("2020-01-01"),
+        ];
+        let values = values.iter().collect::<Vec<_>>();
+
+        assert_eq!(json!("2021-05-04"), Aggregation::First.apply(&values));
+        assert_eq!(
+            json!(["2021-05-04", "2019-11-30", "2020-01-01"]),
+            Aggregation::Unique.apply(&values)
+        );
+        assert_eq!(json!("2019-11-30"), Aggregation::Min.apply(&values));
+        assert_eq!(json!("2021-05-04"), Aggregation::Max.apply(&values));
+        assert_eq!(json!("2021-05-04"), Aggregation::Majority.apply(&values));
+        assert_eq!(Value::Null, Aggregation::Max.apply(&[]));
+    }
+
+    #[test]
+    fn numbers_compared_numerically() {
+        let values = [json!(9), json!(10), json!(2)];
+        let values = values.iter().collect::<Vec<_>>();
+
+        assert_eq!(json!(2), Aggregation::Min.apply(&values));
+        assert_eq!(json!(10), Aggregation::Max.apply(&values));
+        // ties are broken by order of appearance
+        assert_eq!(json!(9), Aggregation::Majority.apply(&values));
+    }
+}
@@ -0,0 +1,428 @@
+use super::coordinates::Coordinates;
+use super::topology::RoofTopology;
+
+/// Largest slope, in degrees, of faces considered flat.
+const FLAT: f64 = 5f64;
+/// Largest difference, in degrees, between azimuths of faces facing the same direction.
+const DIRECTION: f64 = 22.5f64;
+/// Largest difference, in degrees, between slopes of faces at the same level of a direction.
+const LEVEL: f64 = 10f64;
+/// Smallest share of the roof area for a direction to be considered.
+const SIGNIFICANT: f64 = 0.05f64;
+/// Confidence factor applied when the expected ridge or apex is missing.
+const STRUCTURE: f64 = 0.7f64;
+
+/// Shapes of roofs, as queried by planners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoofType {
+    Flat,
+    Shed,
+    Gable,
+    Hip,
+    Pyramid,
+    Mansard,
+    Gambrel,
+    Complex,
+}
+
+/// Shape of a roof with the confidence of the classification, between zero and one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoofClassification {
+    pub roof: RoofType,
+    pub confidence: f64,
+}
+
+/// Sloped faces of a roof facing the same direction.
+struct Direction {
+    /// Azimuth of the downslope direction, in degrees.
+    azimuth: f64,
+    /// Slopes of the distinct levels of faces, such as the steep and shallow parts of a mansard.
+    levels: Vec<f64>,
+    area: f64,
+}
+
+impl RoofType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoofType::Flat => "flat",
+            RoofType::Shed => "shed",
+            RoofType::Gable => "gable",
+            RoofType::Hip => "hip",
+            RoofType::Pyramid => "pyramid",
+            RoofType::Mansard => "mansard",
+            RoofType::Gambrel => "gambrel",
+            RoofType::Complex => "complex",
+        }
+    }
+}
+
+impl RoofClassification {
+    /// Classifies the roof formed by the faces of `topology` from their slopes, the directions they
+    /// face and whether they meet along a horizontal ridge or at a single apex.
+    ///
+    /// The confidence is the share of the roof area following the recognised shape, lowered when
+    /// the ridge or apex this shape implies is missing.
+    pub fn of(topology: &RoofTopology) -> Self {
+        let total = topology.faces.iter().map(|face| face.area).sum::<f64>();
+
+        if topology.faces.is_empty() || total <= 0f64 {
+            return RoofClassification::new(RoofType::Complex, 0f64);
+        }
+
+        let flat = topology
+            .faces
+            .iter()
+            .filter(|face| face.slope < FLAT)
+            .map(|face| face.area)
+            .sum::<f64>();
+
+        if flat >= total {
+            return RoofClassification::new(RoofType::Flat, 1f64);
+        }
+
+        let directions = RoofClassification::directions(topology)
+            .into_iter()
+            .filter(|direction| direction.area >= SIGNIFICANT * total)
+            .collect::<Vec<_>>();
+        let explained = directions
+            .iter()
+            .map(|direction| direction.area)
+            .sum::<f64>()
+            / total;
+        let levels = directions
+            .iter()
+            .map(|direction| direction.levels.len())
+            .max()
+            .unwrap_or(0);
+        let flat = flat / total;
+
+        match (directions.len(), levels) {
+            // mostly flat roofs with some sloped parts, unless sloped parts surround a flat top
+            (count, _) if flat > 0.5f64 && count < 3 => {
+                RoofClassification::new(RoofType::Complex, flat)
+            }
+            (1, 1) if flat < SIGNIFICANT => RoofClassification::new(RoofType::Shed, explained),
+            (2, 1) if flat < SIGNIFICANT && directions[0].is_opposite_to(&directions[1]) => {
+                RoofClassification::structured(
+                    RoofType::Gable,
+                    explained,
+                    RoofClassification::has_ridge(topology),
+                )
+            }
+            (2, 2) if flat < SIGNIFICANT && directions[0].is_opposite_to(&directions[1]) => {
+                RoofClassification::new(RoofType::Gambrel, explained)
+            }
+            (count, 2) if count >= 3 => {
+                RoofClassification::new(RoofType::Mansard, explained + flat)
+            }
+            (count, 1) if count >= 3 && flat > SIGNIFICANT => {
+                // steep sides around a flat top
+                RoofClassification::new(RoofType::Mansard, explained + flat)
+            }
+            (count, 1) if count >= 3 => {
+                if RoofClassification::has_apex(topology) {
+                    RoofClassification::new(RoofType::Pyramid, explained)
+                } else {
+                    RoofClassification::structured(
+                        RoofType::Hip,
+                        explained,
+                        RoofClassification::has_ridge(topology),
+                    )
+                }
+            }
+            _ => RoofClassification::new(RoofType::Complex, 1f64 - explained.min(0.5f64)),
+        }
+    }
+
+    fn new(roof: RoofType, confidence: f64) -> Self {
+        Self {
+            roof,
+            confidence: confidence.clamp(0f64, 1f64),
+        }
+    }
+
+    fn structured(roof: RoofType, confidence: f64, structure: bool) -> Self {
+        if structure {
+            RoofClassification::new(roof, confidence)
+        } else {
+            RoofClassification::new(roof, STRUCTURE * confidence)
+        }
+    }
+
+    /// Sloped faces grouped by direction, the largest first.
+    fn directions(topology: &RoofTopology) -> Vec<Direction> {
+        let mut faces = topology
+            .faces
+            .iter()
+            .filter(|face| face.slope >= FLAT)
+            .collect::<Vec<_>>();
+        faces.sort_by(|a, b| b.area.total_cmp(&a.area));
+
+        let mut directions = Vec::<Direction>::new();
+
+        for face in faces {
+            let azimuth = face.normal.y.atan2(face.normal.x).to_degrees();
+
+            match directions
+                .iter_mut()
+                .find(|direction| angular_distance(direction.azimuth, azimuth) <= DIRECTION)
+            {
+                Some(direction) => {
+                    direction.area += face.area;
+
+                    if direction
+                        .levels
+                        .iter()
+                        .all(|level| (level - face.slope).abs() > LEVEL)
+                    {
+                        direction.levels.push(face.slope);
+                    }
+                }
+                None => directions.push(Direction {
+                    azimuth,
+                    levels: vec![face.slope],
+                    area: face.area,
+                }),
+            }
+        }
+
+        directions.sort_by(|a, b| b.area.total_cmp(&a.area));
+        directions
+    }
+
+    /// Whether two faces meet along a horizontal line forming a ridge.
+    fn has_ridge(topology: &RoofTopology) -> bool {
+        topology.links.iter().any(|link| {
+            let (u, v) = link.line;
+            let length = ((v.x - u.x).powi(2) + (v.y - u.y).powi(2)).sqrt();
+
+            link.dihedral < 180f64 && (v.z - u.z).abs() <= FLAT.to_radians().tan() * length
+        })
+    }
+
+    /// Whether all sloped faces share a single vertex, namely the apex of a pyramid.
+    fn has_apex(topology: &RoofTopology) -> bool {
+        let mut sloped = topology.faces.iter().filter(|face| face.slope >= FLAT);
+
+        match sloped.next() {
+            Some(first) => first.path.sequence.iter().any(|vertex: &Coordinates| {
+                topology
+                    .faces
+                    .iter()
+                    .filter(|face| face.slope >= FLAT)
+                    .all(|face| face.path.contains(vertex))
+            }),
+            None => false,
+        }
+    }
+}
+
+impl Direction {
+    fn is_opposite_to(&self, other: &Self) -> bool {
+        (180f64 - angular_distance(self.azimuth, other.azimuth)).abs() <= DIRECTION
+    }
+}
+
+/// Smallest angle between two azimuths, in degrees.
+fn angular_distance(a: f64, b: f64) -> f64 {
+    let difference = (a - b).rem_euclid(360f64);
+
+    difference.min(360f64 - difference)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::path;
+    use crate::path::Path;
+    use crate::polygon::Polygon;
+    use std::collections::HashMap;
+
+    fn classify(paths: &[Path]) -> RoofClassification {
+        let polygons = paths.iter().map(Polygon::from).collect::<Vec<_>>();
+
+        RoofClassification::of(&RoofTopology::from(&polygons, &HashMap::new()))
+    }
+
+    #[test]
+    fn flat_and_shed_roofs() {
+        let flat = classify(&[path(&[
+            [0.0, 0.0, 3.0],
+            [10.0, 0.0, 3.0],
+            [10.0, 8.0, 3.0],
+            [0.0, 8.0, 3.0],
+        ])]);
+        let shed = classify(&[path(&[
+            [0.0, 0.0, 3.0],
+            [10.0, 0.0, 3.0],
+            [10.0, 8.0, 5.0],
+            [0.0, 8.0, 5.0],
+        ])]);
+
+        assert_eq!(RoofType::Flat, flat.roof);
+        assert_eq!(RoofType::Shed, shed.roof);
+        assert_eq!(1f64, shed.confidence);
+    }
+
+    #[test]
+    fn gable_roof() {
+        let gable = classify(&[
+            path(&[
+                [0.0, 0.0, 0.0],
+                [10.0, 0.0, 0.0],
+                [10.0, 4.0, 3.0],
+                [0.0, 4.0, 3.0],
+            ]),
+            path(&[
+                [0.0, 4.0, 3.0],
+                [10.0, 4.0, 3.0],
+                [10.0, 8.0, 0.0],
+                [0.0, 8.0, 0.0],
+            ]),
+        ]);
+
+        assert_eq!(RoofType::Gable, gable.roof);
+        assert_eq!(1f64, gable.confidence);
+    }
+
+    #[test]
+    fn hip_and_pyramid_roofs() {
+        let hip = classify(&[
+            path(&[
+                [0.0, 0.0, 0.0],
+                [10.0, 0.0, 0.0],
+                [7.0, 3.0, 3.0],
+                [3.0, 3.0, 3.0],
+            ]),
+            path(&[[10.0, 0.0, 0.0], [10.0, 6.0, 0.0], [7.0, 3.0, 3.0]]),
+            path(&[
+                [10.0, 6.0, 0.0],
+                [0.0, 6.0, 0.0],
+                [3.0, 3.0, 3.0],
+                [7.0, 3.0, 3.0],
+            ]),
+            path(&[[0.0, 6.0, 0.0], [0.0, 0.0, 0.0], [3.0, 3.0, 3.0]]),
+        ]);
+        let pyramid = classify(&[
+            path(&[[0.0, 0.0, 0.0], [6.0, 0.0, 0.0], [3.0, 3.0, 3.0]]),
+            path(&[[6.0, 0.0, 0.0], [6.0, 6.0, 0.0], [3.0, 3.0, 3.0]]),
+            path(&[[6.0, 6.0, 0.0], [0.0, 6.0, 0.0], [3.0, 3.0, 3.0]]),
+            path(&[[0.0, 6.0, 0.0], [0.0, 0.0, 0.0], [3.0, 3.0, 3.0]]),
+        ]);
+
+        assert_eq!(RoofType::Hip, hip.roof);
+        assert_eq!(1f64, hip.confidence);
+        assert_eq!(RoofType::Pyramid, pyramid.roof);
+    }
+
+    #[test]
+    fn gambrel_roof() {
+        let gambrel = classify(&[
+            path(&[
+                [0.0, 0.0, 0.0],
+                [10.0, 0.0, 0.0],
+                [10.0, 1.0, 3.0],
+                [0.0, 1.0, 3.0],
+            ]),
+            path(&[
+                [0.0, 1.0, 3.0],
+                [10.0, 1.0, 3.0],
+                [10.0, 4.0, 4.0],
+                [0.0, 4.0, 4.0],
+            ]),
+            path(&[
+                [0.0, 4.0, 4.0],
+                [10.0, 4.0, 4.0],
+                [10.0, 7.0, 3.0],
+                [0.0, 7.0, 3.0],
+            ]),
+            path(&[
+                [0.0, 7.0, 3.0],
+                [10.0, 7.0, 3.0],
+                [10.0, 8.0, 0.0],
+                [0.0, 8.0, 0.0],
+            ]),
+        ]);
+
+        assert_eq!(RoofType::Gambrel, gambrel.roof);
+    }
+
+    #[test]
+    fn mansard_roof() {
+        // steep sides around a flat top
+        let mansard = classify(&[
+            path(&[
+                [0.0, 0.0, 0.0],
+                [10.0, 0.0, 0.0],
+                [9.0, 1.0, 3.0],
+                [1.0, 1.0, 3.0],
+            ]),
+            path(&[
+                [10.0, 0.0, 0.0],
+                [10.0, 8.0, 0.0],
+                [9.0, 7.0, 3.0],
+                [9.0, 1.0, 3.0],
+            ]),
+            path(&[
+                [10.0, 8.0, 0.0],
+                [0.0, 8.0, 0.0],
+                [1.0, 7.0, 3.0],
+                [9.0, 7.0, 3.0],
+            ]),
+            path(&[
+                [0.0, 8.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [1.0, 1.0, 3.0],
+                [1.0, 7.0, 3.0],
+            ]),
+            path(&[
+                [1.0, 1.0, 3.0],
+                [9.0, 1.0, 3.0],
+                [9.0, 7.0, 3.0],
+                [1.0, 7.0, 3.0],
+            ]),
+        ]);
+
+        assert_eq!(RoofType::Mansard, mansard.roof);
+        assert_eq!(1f64, mansard.confidence);
+    }
+
+    #[test]
+    fn complex_roofs() {
+        // sheds facing south and east
+        let perpendicular = classify(&[
+            path(&[
+                [0.0, 0.0, 0.0],
+                [10.0, 0.0, 0.0],
+                [10.0, 5.0, 3.0],
+                [0.0, 5.0, 3.0],
+            ]),
+            path(&[
+                [20.0, 0.0, 3.0],
+                [25.0, 0.0, 0.0],
+                [25.0, 10.0, 0.0],
+                [20.0, 10.0, 3.0],
+            ]),
+        ]);
+        // flat roof with a small dormer
+        let dormer = classify(&[
+            path(&[
+                [0.0, 0.0, 3.0],
+                [20.0, 0.0, 3.0],
+                [20.0, 20.0, 3.0],
+                [0.0, 20.0, 3.0],
+            ]),
+            path(&[
+                [5.0, 5.0, 3.0],
+                [7.0, 5.0, 3.0],
+                [7.0, 7.0, 4.0],
+                [5.0, 7.0, 4.0],
+            ]),
+        ]);
+
+        assert_eq!(RoofType::Complex, perpendicular.roof);
+        assert_eq!(0.5f64, perpendicular.confidence);
+        assert_eq!(RoofType::Complex, dormer.roof);
+        assert!(dormer.confidence > 0.9f64);
+    }
+}
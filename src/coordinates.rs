@@ -1,5 +1,3 @@
-use rand::{rngs::StdRng, Rng, SeedableRng};
-
 /// Coordinates in the three-dimensional plane.
 #[derive(Clone, Copy, Debug)]
 pub struct Coordinates {
@@ -8,7 +6,7 @@ pub struct Coordinates {
     pub z: f64,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct CoordinatesVector {
     pub x: f64,
     pub y: f64,
@@ -70,16 +68,6 @@ impl CoordinatesVector {
         Self { x, y, z }.normalize(f64::EPSILON).unwrap()
     }
 
-    pub fn random() -> Self {
-        let mut generator = StdRng::seed_from_u64(0);
-
-        Self::normalized(
-            generator.gen::<f64>(),
-            generator.gen::<f64>(),
-            generator.gen::<f64>(),
-        )
-    }
-
     pub fn from(connection: &(Coordinates, Coordinates)) -> Self {
         Self::normalized(
             connection.1.x - connection.0.x,
@@ -124,11 +112,51 @@ impl CoordinatesVector {
         }
     }
 
+    /// Orthonormal basis of the plane orthogonal to this unit vector, such that the two vectors
+    /// followed by this one form a right-handed frame.
+    ///
+    /// Built from the vector alone following Duff et al., "Building an Orthonormal Basis,
+    /// Revisited" (2017), which is well conditioned whatever the direction.
+    pub fn orthonormal_basis(&self) -> (Self, Self) {
+        let sign = 1f64.copysign(self.z);
+        let a = -1f64 / (sign + self.z);
+        let b = self.x * self.y * a;
+
+        (
+            Self {
+                x: 1f64 + sign * self.x * self.x * a,
+                y: sign * b,
+                z: -sign * self.x,
+            },
+            Self {
+                x: b,
+                y: sign + self.y * self.y * a,
+                z: -self.y,
+            },
+        )
+    }
+
     pub fn is_parallel_to(&self, other: &Self, epsilon: f64) -> bool {
         self.normal(other, epsilon).is_none()
     }
 
-    fn rescale(&self, multiplier: f64) -> Self {
+    pub fn sum(&self, other: &Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+
+    pub fn rescale(&self, multiplier: f64) -> Self {
         CoordinatesVector {
             x: multiplier * self.x,
             y: multiplier * self.y,
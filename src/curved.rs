@@ -0,0 +1,664 @@
+use indexmap::IndexMap;
+use serde_json::json;
+use serde_json::Value;
+
+use super::coordinates::{Coordinates, CoordinatesVector};
+use super::diagnostics::Diagnostics;
+use super::path::Path;
+use super::tolerances::Tolerances;
+
+/// Largest angle, in degrees, between the normals of faces still lying on a single plane, groups
+/// of such faces being a plane split by helping lines rather than a curved surface.
+const FLAT: f64 = 0.1;
+
+/// Surface fitted through the faces of a curved roof.
+#[derive(Debug, Clone, Copy)]
+pub enum Primitive {
+    /// Cylinder around the line through `point` along the unit `axis`, as a barrel roof.
+    Cylinder {
+        point: Coordinates,
+        axis: CoordinatesVector,
+        radius: f64,
+    },
+    /// Cone opening from its `apex` along the unit `axis`, with the angle in degrees between its
+    /// axis and its surface, as the roof of a round tower.
+    Cone {
+        apex: Coordinates,
+        axis: CoordinatesVector,
+        half_angle: f64,
+    },
+    /// Sphere, as a dome.
+    Sphere { centre: Coordinates, radius: f64 },
+}
+
+/// Adjacent faces approximating a single curved surface, such as the facets of a dome.
+#[derive(Clone)]
+pub struct CurvedSurface {
+    pub faces: Vec<Path>,
+    /// Primitive fitted through the faces, if any fits well.
+    pub primitive: Option<Primitive>,
+}
+
+impl Primitive {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Primitive::Cylinder { .. } => "cylinder",
+            Primitive::Cone { .. } => "cone",
+            Primitive::Sphere { .. } => "sphere",
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let position = |coordinates: &Coordinates| [coordinates.x, coordinates.y, coordinates.z];
+        let direction = |vector: &CoordinatesVector| [vector.x, vector.y, vector.z];
+
+        match self {
+            Primitive::Cylinder {
+                point,
+                axis,
+                radius,
+            } => json!({
+                "type": self.name(),
+                "point": position(point),
+                "axis": direction(axis),
+                "radius": radius,
+            }),
+            Primitive::Cone {
+                apex,
+                axis,
+                half_angle,
+            } => json!({
+                "type": self.name(),
+                "apex": position(apex),
+                "axis": direction(axis),
+                "half_angle": half_angle,
+            }),
+            Primitive::Sphere { centre, radius } => json!({
+                "type": self.name(),
+                "centre": position(centre),
+                "radius": radius,
+            }),
+        }
+    }
+}
+
+/// Groups the faces of `paths` sharing a side and whose normals differ by at most `angle` degrees
+/// into curved surfaces, as digitised for domes, barrel roofs and round towers.
+///
+/// Only groups of faces lying on different planes are curved surfaces. A cylinder, a cone and
+/// then a sphere are fitted through the faces of each one, the first whose normals agree with the
+/// faces within the angle of the `tolerances` and which lies within their distance of the
+/// vertices being kept.
+pub fn curved_surfaces(paths: &[Path], angle: f64, tolerances: &Tolerances) -> Vec<CurvedSurface> {
+    let normals = paths.iter().map(Path::normal).collect::<Vec<_>>();
+    let mut sides = IndexMap::<(Coordinates, Coordinates), Vec<usize>>::new();
+
+    for (index, path) in paths.iter().enumerate() {
+        for (u, v) in path.sides() {
+            sides
+                .entry(Diagnostics::undirected(&u, &v))
+                .or_default()
+                .push(index);
+        }
+    }
+
+    let mut parents = (0..paths.len()).collect::<Vec<_>>();
+
+    for faces in sides.values() {
+        for (i, a) in faces.iter().enumerate() {
+            for b in &faces[(i + 1)..] {
+                if let (Some(n), Some(m)) = (normals[*a], normals[*b]) {
+                    if between(&n, &m) <= angle {
+                        let (a, b) = (root(&mut parents, *a), root(&mut parents, *b));
+
+                        parents[a.max(b)] = a.min(b);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut groups = IndexMap::<usize, Vec<usize>>::new();
+
+    for index in 0..paths.len() {
+        let group = root(&mut parents, index);
+
+        groups.entry(group).or_default().push(index);
+    }
+
+    groups
+        .into_values()
+        .filter(|members| {
+            members.iter().any(|a| {
+                members.iter().any(|b| match (normals[*a], normals[*b]) {
+                    (Some(n), Some(m)) => between(&n, &m) > FLAT,
+                    _ => false,
+                })
+            })
+        })
+        .map(|members| {
+            let faces = members
+                .iter()
+                .map(|index| paths[*index].clone())
+                .collect::<Vec<_>>();
+
+            CurvedSurface {
+                primitive: fit(&faces, tolerances),
+                faces,
+            }
+        })
+        .collect()
+}
+
+/// Representative of the group of `index`, compressing the path to it along the way.
+fn root(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    let mut current = index;
+
+    while parents[current] != root {
+        let parent = parents[current];
+
+        parents[current] = root;
+        current = parent;
+    }
+
+    root
+}
+
+/// Angle, in degrees, between two unit vectors.
+fn between(a: &CoordinatesVector, b: &CoordinatesVector) -> f64 {
+    a.dot(b).clamp(-1f64, 1f64).acos().to_degrees()
+}
+
+/// First primitive fitting the `faces` within the `tolerances`.
+fn fit(faces: &[Path], tolerances: &Tolerances) -> Option<Primitive> {
+    // coordinates are taken relative to a vertex to preserve precision with projected coordinates
+    let origin = faces.first()?.sequence.first().copied()?;
+    let relative = |coordinates: &Coordinates| CoordinatesVector::unscaled(&(origin, *coordinates));
+    let absolute = |vector: &CoordinatesVector| Coordinates {
+        x: origin.x + vector.x,
+        y: origin.y + vector.y,
+        z: origin.z + vector.z,
+    };
+    let mut vertices = Vec::<CoordinatesVector>::new();
+    let mut samples = Vec::<(CoordinatesVector, CoordinatesVector)>::new();
+
+    for face in faces {
+        vertices.extend(face.sequence.iter().skip(1).map(relative));
+        samples.push((relative(&face.centroid()), face.normal()?));
+    }
+
+    // fitting a surface takes at least three faces
+    if samples.len() < 3 {
+        return None;
+    }
+
+    let sine = tolerances.angle.to_radians().sin();
+    let count = samples.len() as f64;
+    let mean = average(samples.iter().map(|(_, normal)| *normal));
+    // cylinders have normals orthogonal to their axis
+    let (spread, axis) = smallest_eigenvector(moments(
+        samples.iter().map(|(_, normal)| *normal),
+        &CoordinatesVector {
+            x: 0f64,
+            y: 0f64,
+            z: 0f64,
+        },
+        count,
+    ));
+
+    if spread.max(0f64).sqrt() <= sine {
+        if let Some((point, radius, residual)) = cylinder(&vertices, &axis) {
+            if residual <= tolerances.distance {
+                return Some(Primitive::Cylinder {
+                    point: absolute(&point),
+                    axis,
+                    radius,
+                });
+            }
+        }
+    }
+    // cones have normals making the same angle with their axis
+    let (spread, axis) = smallest_eigenvector(moments(
+        samples.iter().map(|(_, normal)| *normal),
+        &mean,
+        count,
+    ));
+
+    if spread.max(0f64).sqrt() <= sine {
+        if let Some((apex, axis, half_angle, residual)) = cone(&vertices, &samples, &axis) {
+            if residual <= tolerances.distance {
+                return Some(Primitive::Cone {
+                    apex: absolute(&apex),
+                    axis,
+                    half_angle,
+                });
+            }
+        }
+    }
+    // spheres have normals pointing away from their centre
+    let (centre, radius, residual) = sphere(&vertices)?;
+    let radial = samples.iter().all(|(centroid, normal)| {
+        centroid
+            .difference(&centre)
+            .normalize(f64::EPSILON)
+            .is_some_and(|outwards| {
+                outwards.dot(normal).abs() >= tolerances.angle.to_radians().cos()
+            })
+    });
+
+    (radial && residual <= tolerances.distance).then_some(Primitive::Sphere {
+        centre: absolute(&centre),
+        radius,
+    })
+}
+
+/// Point of the axis along `axis`, radius and root mean square distance from the `vertices` of the
+/// cylinder whose section best fits the vertices projected along its axis.
+fn cylinder(
+    vertices: &[CoordinatesVector],
+    axis: &CoordinatesVector,
+) -> Option<(CoordinatesVector, f64, f64)> {
+    let (u, v) = axis.orthonormal_basis();
+    // circle through the projected vertices, from x² + y² = 2 a x + 2 b y + c
+    let projected = vertices
+        .iter()
+        .map(|vertex| [vertex.dot(&u), vertex.dot(&v)])
+        .collect::<Vec<_>>();
+    let [a, b, c] = least_squares(
+        projected
+            .iter()
+            .map(|[x, y]| ([2f64 * x, 2f64 * y, 1f64], x * x + y * y)),
+    )?;
+    let radius = (c + a * a + b * b).sqrt();
+    let residual = rms(projected.iter().map(|[x, y]| (x - a).hypot(y - b) - radius));
+
+    radius
+        .is_finite()
+        .then_some((u.rescale(a).sum(&v.rescale(b)), radius, residual))
+}
+
+/// Apex, axis pointing into the cone, half angle in degrees and root mean square distance from
+/// the `vertices` of the cone around `axis` best fitting them, its axis crossing the lines along
+/// the normals of the `samples`.
+fn cone(
+    vertices: &[CoordinatesVector],
+    samples: &[(CoordinatesVector, CoordinatesVector)],
+    axis: &CoordinatesVector,
+) -> Option<(CoordinatesVector, CoordinatesVector, f64, f64)> {
+    let (u, v) = axis.orthonormal_basis();
+    // point of the plane orthogonal to the axis closest to the projected normal lines
+    let [a, b] = least_squares(samples.iter().flat_map(|(centroid, normal)| {
+        let point = [centroid.dot(&u), centroid.dot(&v)];
+        let direction = [normal.dot(&u), normal.dot(&v)];
+        let length = direction[0].hypot(direction[1]);
+        let direction = [direction[0] / length, direction[1] / length];
+        // the distance to each line is measured along its own normal in the plane
+        let across = [-direction[1], direction[0]];
+
+        (length > f64::EPSILON).then_some((across, across[0] * point[0] + across[1] * point[1]))
+    }))?;
+    let point = u.rescale(a).sum(&v.rescale(b));
+    // distance to the axis varies linearly along it
+    let profile = vertices
+        .iter()
+        .map(|vertex| {
+            let relative = vertex.difference(&point);
+            let height = relative.dot(axis);
+
+            (height, relative.difference(&axis.rescale(height)).norm())
+        })
+        .collect::<Vec<_>>();
+    let [slope, intercept] = least_squares(
+        profile
+            .iter()
+            .map(|(height, radius)| ([*height, 1f64], *radius)),
+    )?;
+
+    if slope.abs() <= f64::EPSILON {
+        return None;
+    }
+
+    let apex = point.sum(&axis.rescale(-intercept / slope));
+    let half_angle = slope.abs().atan();
+    let residual = rms(profile
+        .iter()
+        .map(|(height, radius)| (radius - slope * height - intercept) * half_angle.cos()));
+
+    Some((
+        apex,
+        axis.rescale(slope.signum()),
+        half_angle.to_degrees(),
+        residual,
+    ))
+}
+
+/// Centre, radius and root mean square distance from the `vertices` of the sphere best fitting
+/// them.
+fn sphere(vertices: &[CoordinatesVector]) -> Option<(CoordinatesVector, f64, f64)> {
+    // from x² + y² + z² = 2 a x + 2 b y + 2 c z + d
+    let [a, b, c, d] = least_squares(vertices.iter().map(|vertex| {
+        (
+            [2f64 * vertex.x, 2f64 * vertex.y, 2f64 * vertex.z, 1f64],
+            vertex.dot(vertex),
+        )
+    }))?;
+    let centre = CoordinatesVector { x: a, y: b, z: c };
+    let radius = (d + centre.dot(&centre)).sqrt();
+    let residual = rms(vertices
+        .iter()
+        .map(|vertex| vertex.difference(&centre).norm() - radius));
+
+    radius.is_finite().then_some((centre, radius, residual))
+}
+
+/// Second moments of the `vectors` about `mean`.
+fn moments(
+    vectors: impl Iterator<Item = CoordinatesVector>,
+    mean: &CoordinatesVector,
+    count: f64,
+) -> [[f64; 3]; 3] {
+    let mut moments = [[0f64; 3]; 3];
+
+    for vector in vectors {
+        let centred = vector.difference(mean);
+        let centred = [centred.x, centred.y, centred.z];
+
+        for i in 0..3 {
+            for j in 0..3 {
+                moments[i][j] += centred[i] * centred[j] / count;
+            }
+        }
+    }
+
+    moments
+}
+
+/// Smallest eigenvalue of the symmetric `matrix` and its unit eigenvector, found with Jacobi
+/// rotations.
+fn smallest_eigenvector(matrix: [[f64; 3]; 3]) -> (f64, CoordinatesVector) {
+    let mut matrix = matrix;
+    let mut vectors = [[1f64, 0f64, 0f64], [0f64, 1f64, 0f64], [0f64, 0f64, 1f64]];
+
+    for _ in 0..32 {
+        let (p, q) = [(0, 1), (0, 2), (1, 2)]
+            .into_iter()
+            .max_by(|(a, b), (c, d)| matrix[*a][*b].abs().total_cmp(&matrix[*c][*d].abs()))
+            .unwrap();
+
+        if matrix[p][q].abs() <= f64::EPSILON * f64::EPSILON {
+            break;
+        }
+
+        let theta = (matrix[q][q] - matrix[p][p]) / (2f64 * matrix[p][q]);
+        let t = 1f64.copysign(theta) / (theta.abs() + (theta * theta + 1f64).sqrt());
+        let c = 1f64 / (t * t + 1f64).sqrt();
+        let s = t * c;
+
+        for row in matrix.iter_mut().chain(vectors.iter_mut()) {
+            let (a, b) = (row[p], row[q]);
+
+            row[p] = c * a - s * b;
+            row[q] = s * a + c * b;
+        }
+
+        let (above, below) = (matrix[p], matrix[q]);
+
+        for (k, (a, b)) in above.into_iter().zip(below).enumerate() {
+            matrix[p][k] = c * a - s * b;
+            matrix[q][k] = s * a + c * b;
+        }
+    }
+
+    let smallest = (0..3)
+        .min_by(|i, j| matrix[*i][*i].total_cmp(&matrix[*j][*j]))
+        .unwrap();
+
+    (
+        matrix[smallest][smallest],
+        CoordinatesVector {
+            x: vectors[0][smallest],
+            y: vectors[1][smallest],
+            z: vectors[2][smallest],
+        },
+    )
+}
+
+/// Solution of the overdetermined linear system given by its `rows`, each one made of its
+/// coefficients and its right-hand side, in the least squares sense.
+fn least_squares<const N: usize>(rows: impl Iterator<Item = ([f64; N], f64)>) -> Option<[f64; N]> {
+    let mut matrix = [[0f64; N]; N];
+    let mut vector = [0f64; N];
+
+    for (coefficients, value) in rows {
+        for i in 0..N {
+            for j in 0..N {
+                matrix[i][j] += coefficients[i] * coefficients[j];
+            }
+
+            vector[i] += coefficients[i] * value;
+        }
+    }
+    // gaussian elimination of the normal equations with partial pivoting
+    for column in 0..N {
+        let pivot = (column..N).max_by(|i, j| {
+            matrix[*i][column]
+                .abs()
+                .total_cmp(&matrix[*j][column].abs())
+        })?;
+
+        if matrix[pivot][column].abs() <= f64::EPSILON {
+            return None;
+        }
+
+        matrix.swap(column, pivot);
+        vector.swap(column, pivot);
+
+        let pivot = matrix[column];
+
+        for row in (column + 1)..N {
+            let factor = matrix[row][column] / pivot[column];
+
+            for (value, reference) in matrix[row].iter_mut().zip(pivot).skip(column) {
+                *value -= factor * reference;
+            }
+
+            vector[row] -= factor * vector[column];
+        }
+    }
+
+    let mut solution = [0f64; N];
+
+    for row in (0..N).rev() {
+        let known = ((row + 1)..N)
+            .map(|k| matrix[row][k] * solution[k])
+            .sum::<f64>();
+
+        solution[row] = (vector[row] - known) / matrix[row][row];
+    }
+
+    Some(solution)
+}
+
+fn average(vectors: impl Iterator<Item = CoordinatesVector>) -> CoordinatesVector {
+    let mut total = CoordinatesVector {
+        x: 0f64,
+        y: 0f64,
+        z: 0f64,
+    };
+    let mut count = 0f64;
+
+    for vector in vectors {
+        total = total.sum(&vector);
+        count += 1f64;
+    }
+
+    total.rescale(1f64 / count.max(1f64))
+}
+
+fn rms(values: impl Iterator<Item = f64>) -> f64 {
+    let (mut total, mut count) = (0f64, 0f64);
+
+    for value in values {
+        total += value * value;
+        count += 1f64;
+    }
+
+    (total / count.max(1f64)).sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::path;
+
+    const TOLERANCES: Tolerances = Tolerances {
+        angle: 5.0,
+        distance: 0.05,
+        snapping: 0.0,
+        angle_sum: 5.0,
+    };
+
+    /// Path along `ring`, its normal pointing upwards.
+    fn face(ring: &[[f64; 3]]) -> Path {
+        path(ring).reverse_if_normal_is_negative()
+    }
+
+    /// Point at `elevation` and `azimuth`, in degrees, on the sphere of `radius` around `centre`.
+    fn spherical(centre: [f64; 3], radius: f64, elevation: f64, azimuth: f64) -> [f64; 3] {
+        let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+
+        [
+            centre[0] + radius * elevation.cos() * azimuth.cos(),
+            centre[1] + radius * elevation.cos() * azimuth.sin(),
+            centre[2] + radius * elevation.sin(),
+        ]
+    }
+
+    #[test]
+    fn barrel_roof_is_a_cylinder() {
+        // half cylinder of radius 4 along x, in six facets, next to a gable roof
+        let arc = |step: usize| {
+            let angle = (30 * step) as f64;
+
+            spherical([0., 0., 5.], 4., angle, 90.)
+        };
+        let mut paths = (0..6)
+            .map(|step| {
+                let (a, b) = (arc(step), arc(step + 1));
+
+                face(&[a, b, [10., b[1], b[2]], [10., a[1], a[2]]])
+            })
+            .collect::<Vec<_>>();
+
+        paths.push(face(&[
+            [20., 0., 5.],
+            [30., 0., 5.],
+            [30., 4., 8.],
+            [20., 4., 8.],
+        ]));
+        paths.push(face(&[
+            [20., 4., 8.],
+            [30., 4., 8.],
+            [30., 8., 5.],
+            [20., 8., 5.],
+        ]));
+
+        let surfaces = curved_surfaces(&paths, 35., &TOLERANCES);
+
+        assert_eq!(1, surfaces.len());
+        assert_eq!(6, surfaces[0].faces.len());
+
+        match surfaces[0].primitive {
+            Some(Primitive::Cylinder {
+                point,
+                axis,
+                radius,
+            }) => {
+                assert!((radius - 4.).abs() < 1e-6);
+                assert!((axis.x.abs() - 1.).abs() < 1e-6);
+                assert!(point.y.abs() < 1e-6 && (point.z - 5.).abs() < 1e-6);
+            }
+            primitive => panic!("{primitive:?}"),
+        }
+    }
+
+    #[test]
+    fn tower_roof_is_a_cone() {
+        // eight facets rising from a circle of radius 5 to an apex 5 above
+        let base = |step: usize| spherical([0., 0., 5.], 5., 0., (45 * step) as f64);
+        let paths = (0..8)
+            .map(|step| face(&[base(step), base(step + 1), [0., 0., 10.]]))
+            .collect::<Vec<_>>();
+        let surfaces = curved_surfaces(&paths, 35., &TOLERANCES);
+
+        assert_eq!(1, surfaces.len());
+
+        match surfaces[0].primitive {
+            Some(Primitive::Cone {
+                apex,
+                axis,
+                half_angle,
+            }) => {
+                assert!((half_angle - 45.).abs() < 1e-6);
+                assert!((axis.z + 1.).abs() < 1e-6);
+                assert!(apex.x.abs() < 1e-6 && apex.y.abs() < 1e-6 && (apex.z - 10.).abs() < 1e-6);
+            }
+            primitive => panic!("{primitive:?}"),
+        }
+    }
+
+    #[test]
+    fn dome_is_a_sphere() {
+        // rings of eight facets between elevations, closed by a fan of triangles
+        let elevations = [10., 35., 60.];
+        let point = |ring: usize, step: usize| {
+            spherical([3., 4., 2.], 6., elevations[ring], (45 * step) as f64)
+        };
+        let mut paths = Vec::<Path>::new();
+
+        for ring in 0..2 {
+            for step in 0..8 {
+                paths.push(face(&[
+                    point(ring, step),
+                    point(ring, step + 1),
+                    point(ring + 1, step + 1),
+                    point(ring + 1, step),
+                ]));
+            }
+        }
+
+        for step in 0..8 {
+            paths.push(face(&[point(2, step), point(2, step + 1), [3., 4., 8.]]));
+        }
+
+        let surfaces = curved_surfaces(&paths, 40., &TOLERANCES);
+
+        assert_eq!(1, surfaces.len());
+        assert_eq!(24, surfaces[0].faces.len());
+
+        match surfaces[0].primitive {
+            Some(Primitive::Sphere { centre, radius }) => {
+                assert!((radius - 6.).abs() < 1e-6);
+                assert!((centre.x - 3.).abs() < 1e-6 && (centre.z - 2.).abs() < 1e-6);
+            }
+            primitive => panic!("{primitive:?}"),
+        }
+    }
+
+    #[test]
+    fn split_planes_are_not_curved() {
+        // flat roof split in two by a helping line
+        let paths = [
+            face(&[[0., 0., 3.], [5., 0., 3.], [5., 8., 3.], [0., 8., 3.]]),
+            face(&[[5., 0., 3.], [10., 0., 3.], [10., 8., 3.], [5., 8., 3.]]),
+        ];
+
+        assert!(curved_surfaces(&paths, 35., &TOLERANCES).is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+
+use super::path::Path;
+use super::pathgraph::{Edge, PathGraph, VertexId};
+use super::plane::PlaneMatcher;
+use super::tolerances::Tolerances;
+
+type HalfEdge = Edge;
+
+/// Doubly-connected edge list of the lines lying on a single plane, each half-edge pointing to
+/// the half-edge following it around the face on its left, the twin of a half-edge being the
+/// same line walked the other way.
+struct Subdivision {
+    plane: PlaneMatcher,
+    next: IndexMap<HalfEdge, HalfEdge>,
+}
+
+/// Enumerates the faces of a path graph plane by plane, as an alternative to the depth-first
+/// search of [`PathBuilder`](super::path::PathBuilder).
+///
+/// Each half-edge is visited once per plane it lies on, so that faces are read off without
+/// enumerating the many rings the lines could form.
+pub struct FaceBuilder<'a> {
+    graph: &'a PathGraph,
+    tolerances: Tolerances,
+}
+
+impl Subdivision {
+    /// Closed rings of the subdivision, as vertices of `graph`, walked from every half-edge not
+    /// yet met.
+    fn faces(&self, graph: &PathGraph) -> Vec<Path> {
+        let mut visited = IndexSet::<HalfEdge>::new();
+        let mut faces = Vec::<Path>::new();
+
+        for start in self.next.keys() {
+            let mut sequence = Vec::<VertexId>::new();
+            let mut current = *start;
+
+            while visited.insert(current) {
+                sequence.push(current.0);
+
+                match self.next.get(&current) {
+                    Some(next) => current = *next,
+                    None => break,
+                }
+            }
+            // rings are only closed when walking back to where they started
+            if current == *start && sequence.len() > 2 {
+                faces.push(Path::from(&graph.ring(&sequence)));
+            }
+        }
+
+        faces
+    }
+}
+
+impl<'a> FaceBuilder<'a> {
+    pub fn from(graph: &'a PathGraph) -> Self {
+        Self {
+            graph,
+            tolerances: graph.tolerances,
+        }
+    }
+
+    /// Overrides the tolerances used to validate rings, which default to those the graph was built with.
+    pub fn tolerances(mut self, tolerances: &Tolerances) -> Self {
+        self.tolerances = *tolerances;
+        self
+    }
+
+    pub fn build(self) -> IndexSet<Path> {
+        let mut paths = IndexSet::<Path>::new();
+
+        for subdivision in self.subdivisions() {
+            for path in subdivision.faces(self.graph) {
+                // the unbounded face walks around the plane the wrong way and is rejected here
+                if path.is_valid_on(&subdivision.plane, &self.tolerances) {
+                    paths.insert(path.reverse_if_normal_is_negative());
+                }
+            }
+        }
+
+        paths
+    }
+
+    /// Subdivisions of the distinct planes of the graph, following the successors the graph
+    /// computed on each plane, the successor along collinear lines applying to any plane, just
+    /// like the only successor of a half-edge.
+    ///
+    /// Half-edges are bucketed by plane in a single pass over the graph, the first plane met
+    /// standing for all the planes matching it within the tolerances.
+    fn subdivisions(&self) -> Vec<Subdivision> {
+        let mut subdivisions = Vec::<Subdivision>::new();
+        // successors applying to any plane lacking one of its own
+        let mut fallbacks = Vec::<(HalfEdge, HalfEdge)>::new();
+
+        for (incident, matchers) in &self.graph.intersections {
+            for (matcher, successor) in matchers {
+                if matcher.is_undefined() {
+                    continue;
+                }
+
+                match subdivisions
+                    .iter_mut()
+                    .find(|subdivision| subdivision.plane == *matcher)
+                {
+                    Some(subdivision) => {
+                        subdivision.next.entry(*incident).or_insert(*successor);
+                    }
+                    None => subdivisions.push(Subdivision {
+                        plane: *matcher,
+                        next: IndexMap::from([(*incident, *successor)]),
+                    }),
+                }
+            }
+
+            if let Some((_, successor)) = matchers
+                .iter()
+                .find(|(matcher, _)| matcher.is_undefined())
+                .or_else(|| matchers.first().filter(|_| matchers.len() == 1))
+            {
+                fallbacks.push((*incident, *successor));
+            }
+        }
+
+        for subdivision in &mut subdivisions {
+            for (incident, successor) in &fallbacks {
+                subdivision.next.entry(*incident).or_insert(*successor);
+            }
+        }
+
+        subdivisions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::coordinates::Coordinates;
+    use crate::fixtures::{gable, line};
+    use crate::path::PathBuilder;
+    use crate::pathgraph::PathGraphBuilder;
+    use crate::pipeline::{polygonalize_with, Engine, TOLERANCES};
+    use std::collections::BTreeSet;
+
+    fn fixtures() -> Vec<Vec<(Coordinates, Coordinates)>> {
+        vec![
+            // two planes meeting along a line split in two, with dead ends
+            vec![
+                line([0.0, 0.0, 0.0], [7.0, 0.0, 0.0]),
+                line([7.0, 0.0, 0.0], [10.0, 0.0, 0.0]),
+                line([0.0, 0.0, 0.0], [0.0, 25.0, 15.0]),
+                line([10.0, 0.0, 0.0], [10.0, 25.0, 15.0]),
+                line([0.0, 25.0, 15.0], [10.0, 25.0, 15.0]),
+                line([0.0, 0.0, 0.0], [0.0, 5.0, -5.0]),
+                line([7.0, 0.0, 0.0], [7.0, 5.0, -5.0]),
+                line([0.0, 5.0, -5.0], [7.0, 5.0, -5.0]),
+            ],
+            gable(),
+            // hip roof
+            vec![
+                line([0.0, 0.0, 0.0], [10.0, 0.0, 0.0]),
+                line([10.0, 0.0, 0.0], [10.0, 6.0, 0.0]),
+                line([10.0, 6.0, 0.0], [0.0, 6.0, 0.0]),
+                line([0.0, 6.0, 0.0], [0.0, 0.0, 0.0]),
+                line([0.0, 0.0, 0.0], [3.0, 3.0, 3.0]),
+                line([10.0, 0.0, 0.0], [7.0, 3.0, 3.0]),
+                line([10.0, 6.0, 0.0], [7.0, 3.0, 3.0]),
+                line([0.0, 6.0, 0.0], [3.0, 3.0, 3.0]),
+                line([3.0, 3.0, 3.0], [7.0, 3.0, 3.0]),
+            ],
+            // flat roof split in two by a helping line
+            vec![
+                line([0.0, 0.0, 3.0], [10.0, 0.0, 3.0]),
+                line([10.0, 0.0, 3.0], [10.0, 8.0, 3.0]),
+                line([10.0, 8.0, 3.0], [0.0, 8.0, 3.0]),
+                line([0.0, 8.0, 3.0], [0.0, 0.0, 3.0]),
+                line([5.0, 0.0, 3.0], [5.0, 8.0, 3.0]),
+            ],
+        ]
+    }
+
+    fn identifiers<'a>(paths: impl IntoIterator<Item = &'a Path>) -> BTreeSet<String> {
+        paths.into_iter().map(|path| path.identifier("")).collect()
+    }
+
+    #[test]
+    fn faces_are_found_once_per_plane() {
+        let lines = &fixtures()[1];
+        let graph = PathGraphBuilder::from(lines, &TOLERANCES[0]).build();
+
+        assert_eq!(2, FaceBuilder::from(&graph).build().len());
+    }
+
+    #[test]
+    fn engines_agree_on_fixtures() {
+        for lines in fixtures() {
+            let expected = polygonalize_with(&lines, &TOLERANCES, Engine::DepthFirst).0;
+            let found = polygonalize_with(&lines, &TOLERANCES, Engine::HalfEdge).0;
+
+            assert_eq!(identifiers(&expected), identifiers(&found));
+        }
+        // a single graph gives the same rings before filtering
+        for lines in fixtures() {
+            let graph = PathGraphBuilder::from(&lines, &TOLERANCES[1]).build();
+
+            assert_eq!(
+                identifiers(&PathBuilder::from(&graph).build()),
+                identifiers(&FaceBuilder::from(&graph).build())
+            );
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use indexmap::IndexMap;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashSet;
+
+use super::coordinates::Coordinates;
+use super::path::Path;
+use super::pathgraph::{PathGraph, PathGraphBuilder};
+use super::tolerances::Tolerances;
+
+/// Problems met while extracting polygons from a set of lines, to be reviewed for quality assurance.
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    /// Dangling chains of lines removed before searching for rings, each one starting at its free end.
+    pub pruned: Vec<Vec<Coordinates>>,
+    /// Lines that are not a side of any polygon, pruned lines excluded.
+    pub uncovered: Vec<(Coordinates, Coordinates)>,
+    /// Lines that are a side of more than two polygons, with the number of polygons.
+    pub overcovered: Vec<((Coordinates, Coordinates), usize)>,
+    /// Intersections where two lines are collinear and hence define no plane, as the sequence of
+    /// the three coordinates involved.
+    pub undefined: Vec<(Coordinates, Coordinates, Coordinates)>,
+    /// Lines missing from the input and inferred to close open faces.
+    pub synthetic: Vec<(Coordinates, Coordinates)>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the pruned chains and undefined intersections of a path `graph`, skipping those
+    /// already recorded from a graph built with other tolerances.
+    pub fn record(&mut self, graph: &PathGraph) {
+        for chain in &graph.pruned {
+            if !self.pruned.contains(chain) {
+                self.pruned.push(chain.clone());
+            }
+        }
+
+        for junction in &graph.undefined {
+            if !self.undefined.contains(junction) {
+                self.undefined.push(*junction);
+            }
+        }
+    }
+
+    /// Records the input `lines` used by too few or too many of the `paths` formed from them with
+    /// the `tolerances`, lines being related to the sides of the paths through their endpoints as
+    /// snapped with each of the `tolerances`.
+    pub fn cover(
+        &mut self,
+        lines: &[(Coordinates, Coordinates)],
+        paths: &[Path],
+        tolerances: &[Tolerances],
+    ) {
+        let coverage = Diagnostics::coverage(paths);
+        let pruned = self
+            .pruned
+            .iter()
+            .flat_map(|chain| {
+                chain
+                    .windows(2)
+                    .map(|side| Diagnostics::undirected(&side[0], &side[1]))
+            })
+            .collect::<HashSet<_>>();
+
+        for ((u, v), candidates) in lines.iter().zip(Diagnostics::candidates(lines, tolerances)) {
+            // lines collapsing to a single point are merged into the lines around them
+            if candidates.iter().any(|(a, b)| a == b) {
+                continue;
+            }
+
+            match candidates
+                .iter()
+                .filter_map(|line| coverage.get(line))
+                .max()
+            {
+                None if !candidates.iter().any(|line| pruned.contains(line)) => {
+                    self.uncovered.push((*u, *v))
+                }
+                Some(count) if *count > 2 => self.overcovered.push(((*u, *v), *count)),
+                _ => (),
+            }
+        }
+    }
+
+    /// Sides of paths each of the `lines` may have become, whatever their direction: the line as
+    /// digitised, then with its endpoints as snapped while building the graph with each of the
+    /// `tolerances`.
+    pub fn candidates(
+        lines: &[(Coordinates, Coordinates)],
+        tolerances: &[Tolerances],
+    ) -> Vec<Vec<(Coordinates, Coordinates)>> {
+        let snapped = tolerances
+            .iter()
+            .filter(|tolerances| tolerances.snapping > 0f64)
+            .map(|tolerances| PathGraphBuilder::snapped(lines, tolerances.snapping))
+            .collect::<Vec<_>>();
+
+        lines
+            .iter()
+            .map(|(u, v)| {
+                std::iter::once(Diagnostics::undirected(u, v))
+                    .chain(snapped.iter().map(|vertices| {
+                        let snap = |coordinates| *vertices.get(coordinates).unwrap_or(coordinates);
+
+                        Diagnostics::undirected(&snap(u), &snap(v))
+                    }))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Merges diagnostics of another set of lines, such as another building.
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.pruned.extend(other.pruned);
+        self.uncovered.extend(other.uncovered);
+        self.overcovered.extend(other.overcovered);
+        self.undefined.extend(other.undefined);
+        self.synthetic.extend(other.synthetic);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pruned.is_empty()
+            && self.uncovered.is_empty()
+            && self.overcovered.is_empty()
+            && self.undefined.is_empty()
+            && self.synthetic.is_empty()
+    }
+
+    /// Number of `paths` each line is a side of, whatever its direction.
+    pub fn coverage(paths: &[Path]) -> IndexMap<(Coordinates, Coordinates), usize> {
+        let mut coverage = IndexMap::<(Coordinates, Coordinates), usize>::new();
+
+        for path in paths {
+            for (u, v) in path.sides() {
+                *coverage.entry(Diagnostics::undirected(&u, &v)).or_default() += 1;
+            }
+        }
+
+        coverage
+    }
+
+    /// Line with its extremities sorted, so that it is the same whichever way it was digitised.
+    pub fn undirected(u: &Coordinates, v: &Coordinates) -> (Coordinates, Coordinates) {
+        if u <= v {
+            (*u, *v)
+        } else {
+            (*v, *u)
+        }
+    }
+
+    /// Geojson line features locating each problem, with its kind as `issue` property.
+    pub fn features(&self) -> Vec<Value> {
+        let feature = |issue: &str, coordinates: Vec<&Coordinates>, count: Option<usize>| {
+            json!({
+                "type": "Feature",
+                "properties": {
+                    "issue": issue,
+                    "count": count
+                },
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": coordinates
+                        .iter()
+                        .map(|coordinates| [ coordinates.x, coordinates.y, coordinates.z ])
+                        .collect::<Vec<_>>()
+                }
+            })
+        };
+
+        self.pruned
+            .iter()
+            .map(|chain| feature("pruned", chain.iter().collect(), None))
+            .chain(
+                self.uncovered
+                    .iter()
+                    .map(|(u, v)| feature("uncovered", vec![u, v], Some(0))),
+            )
+            .chain(
+                self.overcovered
+                    .iter()
+                    .map(|((u, v), count)| feature("overcovered", vec![u, v], Some(*count))),
+            )
+            .chain(
+                self.undefined
+                    .iter()
+                    .map(|(u, w, v)| feature("undefined", vec![u, w, v], None)),
+            )
+            .chain(
+                self.synthetic
+                    .iter()
+                    .map(|(u, v)| feature("synthetic", vec![u, v], None)),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::line;
+    use crate::pipeline::{polygonalize_diagnosed, TOLERANCES};
+
+    #[test]
+    fn dangling_and_collinear_lines_are_reported() {
+        // one plane with two dead ends and a line split in two collinear parts
+        let lines = vec![
+            line([0.0, 0.0, 0.0], [7.0, 0.0, 0.0]),
+            line([7.0, 0.0, 0.0], [10.0, 0.0, 0.0]),
+            line([0.0, 0.0, 0.0], [0.0, 25.0, 15.0]),
+            line([10.0, 0.0, 0.0], [10.0, 25.0, 15.0]),
+            line([0.0, 25.0, 15.0], [10.0, 25.0, 15.0]),
+            line([0.0, 0.0, 0.0], [0.0, 5.0, -5.0]),
+            line([7.0, 0.0, 0.0], [7.0, 5.0, -5.0]),
+        ];
+        let (paths, diagnostics) = polygonalize_diagnosed(&lines, &TOLERANCES);
+
+        assert_eq!(1, paths.len());
+        assert_eq!(
+            vec![vec![lines[5].1, lines[5].0], vec![lines[6].1, lines[6].0]],
+            diagnostics.pruned
+        );
+        assert_eq!(
+            vec![(lines[0].0, lines[0].1, lines[1].1)],
+            diagnostics.undefined
+        );
+        assert!(diagnostics.uncovered.is_empty());
+        assert!(diagnostics.overcovered.is_empty());
+        assert_eq!(3, diagnostics.features().len());
+    }
+
+    #[test]
+    fn snapped_lines_are_covered() {
+        // a square whose last line was digitised slightly off its first corner
+        let lines = vec![
+            line([0.0, 0.0, 0.0], [10.0, 0.0, 0.0]),
+            line([10.0, 0.0, 0.0], [10.0, 10.0, 0.0]),
+            line([10.0, 10.0, 0.0], [0.0, 10.0, 0.0]),
+            line([0.0, 10.0, 0.0], [0.02, 0.01, 0.0]),
+        ];
+        let tolerances = [Tolerances {
+            snapping: 0.05,
+            ..Tolerances::default()
+        }];
+        let (paths, diagnostics) = polygonalize_diagnosed(&lines, &tolerances);
+
+        assert_eq!(1, paths.len());
+        assert!(diagnostics.uncovered.is_empty());
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn chains_meeting_are_pruned_as_one() {
+        let lines = vec![
+            line([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]),
+            line([1.0, 0.0, 0.0], [2.0, 1.0, 0.0]),
+        ];
+        let (paths, diagnostics) = polygonalize_diagnosed(&lines, &TOLERANCES);
+
+        assert!(paths.is_empty());
+        assert_eq!(
+            vec![vec![lines[0].0, lines[0].1, lines[1].1]],
+            diagnostics.pruned
+        );
+        assert!(diagnostics.uncovered.is_empty());
+    }
+}
@@ -0,0 +1,371 @@
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+use serde_json::json;
+use serde_json::Value;
+
+use super::coordinates::CoordinatesVector;
+use super::path::Path;
+use super::tolerances::Tolerances;
+
+/// Smallest intersection over union of a predicted face and a reference face for them to match.
+pub const MATCHING_IOU: f64 = 0.5;
+
+/// Number of samples along each axis of the rectangle bounding two faces when measuring their
+/// overlap.
+const SAMPLES: usize = 128;
+
+/// Predicted face paired with the reference face it was matched to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaceMatch {
+    /// Index of the predicted face.
+    pub predicted: usize,
+    /// Index of the reference face.
+    pub reference: usize,
+    /// Intersection over union of both faces, measured in the plane of the reference face.
+    pub iou: f64,
+    /// Angle, in degrees, between the normals of both faces.
+    pub normal_error: f64,
+}
+
+/// Accuracy of the faces predicted for a building against its reference faces.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Evaluation {
+    /// Number of predicted faces.
+    pub predicted: usize,
+    /// Number of reference faces.
+    pub references: usize,
+    /// Pairs of faces matched one to one.
+    pub matches: Vec<FaceMatch>,
+}
+
+/// Accuracy of the faces predicted for each building of a dataset.
+#[derive(Debug, Clone, Default)]
+pub struct DatasetEvaluation {
+    pub buildings: IndexMap<String, Evaluation>,
+}
+
+impl Evaluation {
+    /// Share of the predicted faces matching a reference face, one when nothing was predicted.
+    pub fn precision(&self) -> f64 {
+        Self::ratio(self.matches.len(), self.predicted)
+    }
+
+    /// Share of the reference faces matched by a predicted face, one when there is no reference.
+    pub fn recall(&self) -> f64 {
+        Self::ratio(self.matches.len(), self.references)
+    }
+
+    /// Average intersection over union of the matched faces, if any.
+    pub fn mean_iou(&self) -> Option<f64> {
+        self.mean(|found| found.iou)
+    }
+
+    /// Average angle, in degrees, between the normals of the matched faces, if any.
+    pub fn mean_normal_error(&self) -> Option<f64> {
+        self.mean(|found| found.normal_error)
+    }
+
+    /// Pools the faces of `other` with these ones, as when summarising several buildings.
+    pub fn extend(&mut self, other: &Self) {
+        let (predicted, references) = (self.predicted, self.references);
+
+        self.matches
+            .extend(other.matches.iter().map(|found| FaceMatch {
+                predicted: predicted + found.predicted,
+                reference: references + found.reference,
+                ..*found
+            }));
+        self.predicted += other.predicted;
+        self.references += other.references;
+    }
+
+    /// Summary of the evaluation as json.
+    pub fn summary(&self) -> Value {
+        json!({
+            "predicted": self.predicted,
+            "references": self.references,
+            "matched": self.matches.len(),
+            "precision": self.precision(),
+            "recall": self.recall(),
+            "mean_iou": self.mean_iou(),
+            "mean_normal_error": self.mean_normal_error(),
+        })
+    }
+
+    fn ratio(matched: usize, total: usize) -> f64 {
+        if total == 0 {
+            1f64
+        } else {
+            matched as f64 / total as f64
+        }
+    }
+
+    fn mean(&self, value: impl Fn(&FaceMatch) -> f64) -> Option<f64> {
+        (!self.matches.is_empty())
+            .then(|| self.matches.iter().map(value).sum::<f64>() / self.matches.len() as f64)
+    }
+}
+
+impl DatasetEvaluation {
+    /// Evaluation of all the buildings pooled together.
+    pub fn total(&self) -> Evaluation {
+        let mut total = Evaluation::default();
+
+        for evaluation in self.buildings.values() {
+            total.extend(evaluation);
+        }
+
+        total
+    }
+
+    /// Summary of each building and of the whole dataset as json.
+    pub fn summary(&self) -> Value {
+        json!({
+            "dataset": self.total().summary(),
+            "buildings": self
+                .buildings
+                .iter()
+                .map(|(building, evaluation)| (building.clone(), evaluation.summary()))
+                .collect::<serde_json::Map<_, _>>(),
+        })
+    }
+}
+
+/// Matches the `predicted` faces of a building to its `reference` faces.
+///
+/// Two faces can only match when the angle between their normals and the distance from the
+/// vertices of the predicted face to the plane of the reference face are within the
+/// `tolerances`, and when their intersection over union is at least [`MATCHING_IOU`]. Pairs
+/// overlapping most are matched first, each face being matched once.
+pub fn evaluate(predicted: &[Path], reference: &[Path], tolerances: &Tolerances) -> Evaluation {
+    let mut candidates = Vec::<FaceMatch>::new();
+
+    for (i, face) in predicted.iter().enumerate() {
+        for (j, other) in reference.iter().enumerate() {
+            if let Some(found) = matching(face, other, tolerances) {
+                candidates.push(FaceMatch {
+                    predicted: i,
+                    reference: j,
+                    ..found
+                });
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.iou.total_cmp(&a.iou));
+
+    let mut matched = (vec![false; predicted.len()], vec![false; reference.len()]);
+    let mut matches = Vec::<FaceMatch>::new();
+
+    for found in candidates {
+        if !matched.0[found.predicted] && !matched.1[found.reference] {
+            matched.0[found.predicted] = true;
+            matched.1[found.reference] = true;
+            matches.push(found);
+        }
+    }
+
+    matches.sort_by_key(|found| found.predicted);
+
+    Evaluation {
+        predicted: predicted.len(),
+        references: reference.len(),
+        matches,
+    }
+}
+
+/// Evaluates each building of the `predicted` faces against the faces of the same building in
+/// `reference`, buildings missing on either side having no face there.
+pub fn evaluate_groups(
+    predicted: &IndexMap<String, Vec<Path>>,
+    reference: &IndexMap<String, Vec<Path>>,
+    tolerances: &Tolerances,
+) -> DatasetEvaluation {
+    let none = Vec::<Path>::new();
+    let buildings = reference
+        .keys()
+        .chain(predicted.keys())
+        .collect::<IndexSet<_>>()
+        .into_iter()
+        .map(|building| {
+            (
+                building.clone(),
+                evaluate(
+                    predicted.get(building).unwrap_or(&none),
+                    reference.get(building).unwrap_or(&none),
+                    tolerances,
+                ),
+            )
+        })
+        .collect();
+
+    DatasetEvaluation { buildings }
+}
+
+/// Match of `face` against `other`, both indices being left to zero, when both lie on the same
+/// plane and overlap enough.
+fn matching(face: &Path, other: &Path, tolerances: &Tolerances) -> Option<FaceMatch> {
+    let (normal, reference) = (face.normal()?, other.normal()?);
+    // faces may be walked either way
+    let normal_error = normal.dot(&reference).abs().min(1f64).acos().to_degrees();
+
+    if normal_error > tolerances.angle {
+        return None;
+    }
+
+    let origin = other.sequence[0];
+    let on_plane = face.sequence.iter().all(|coordinates| {
+        reference
+            .dot(&CoordinatesVector::unscaled(&(origin, *coordinates)))
+            .abs()
+            <= tolerances.distance
+    });
+
+    if !on_plane {
+        return None;
+    }
+
+    let iou = iou(face, other, &reference);
+
+    (iou >= MATCHING_IOU).then_some(FaceMatch {
+        predicted: 0,
+        reference: 0,
+        iou,
+        normal_error,
+    })
+}
+
+/// Intersection over union of two faces projected onto the plane orthogonal to `normal`, sampled
+/// on a regular grid over the rectangle bounding both.
+fn iou(face: &Path, other: &Path, normal: &CoordinatesVector) -> f64 {
+    let (u, v) = normal.orthonormal_basis();
+    let projected = |path: &Path| {
+        path.sequence
+            .iter()
+            .map(|coordinates| {
+                let vector = CoordinatesVector {
+                    x: coordinates.x,
+                    y: coordinates.y,
+                    z: coordinates.z,
+                };
+
+                (vector.dot(&u), vector.dot(&v))
+            })
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (projected(face), projected(other));
+    let (mut min, mut max) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+
+    for (x, y) in a.iter().chain(&b) {
+        min = (min.0.min(*x), min.1.min(*y));
+        max = (max.0.max(*x), max.1.max(*y));
+    }
+
+    let step = (
+        (max.0 - min.0) / SAMPLES as f64,
+        (max.1 - min.1) / SAMPLES as f64,
+    );
+    let (mut intersection, mut union) = (0usize, 0usize);
+
+    for i in 0..SAMPLES {
+        for j in 0..SAMPLES {
+            // samples the centre of each cell
+            let point = (
+                min.0 + (i as f64 + 0.5) * step.0,
+                min.1 + (j as f64 + 0.5) * step.1,
+            );
+            let (inside, inside_other) = (contains(&a, point), contains(&b, point));
+
+            intersection += (inside && inside_other) as usize;
+            union += (inside || inside_other) as usize;
+        }
+    }
+
+    if union == 0 {
+        0f64
+    } else {
+        intersection as f64 / union as f64
+    }
+}
+
+/// Whether `point` lies within the closed `ring`, by the even-odd rule.
+fn contains(ring: &[(f64, f64)], point: (f64, f64)) -> bool {
+    let mut inside = false;
+
+    for (a, b) in ring.iter().zip(ring.iter().skip(1)) {
+        if (a.1 > point.1) != (b.1 > point.1)
+            && point.0 < a.0 + (point.1 - a.1) / (b.1 - a.1) * (b.0 - a.0)
+        {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::path;
+
+    #[test]
+    fn faces_are_matched_by_plane_and_overlap() {
+        // gable roof
+        let reference = [
+            path(&[[0., 0., 5.], [10., 0., 5.], [10., 4., 8.], [0., 4., 8.]]),
+            path(&[[0., 4., 8.], [10., 4., 8.], [10., 8., 5.], [0., 8., 5.]]),
+        ];
+        // the southern face found too short, the northern one walked the other way, and a
+        // face lying on the ground below it
+        let predicted = [
+            path(&[[0., 0., 5.], [4., 0., 5.], [4., 4., 8.], [0., 4., 8.]]),
+            path(&[[0., 8., 5.], [10., 8., 5.], [10., 4., 8.], [0., 4., 8.]]),
+            path(&[[0., 4., 0.], [10., 4., 0.], [10., 8., 0.], [0., 8., 0.]]),
+        ];
+        let evaluation = evaluate(&predicted, &reference, &Tolerances::default());
+
+        assert_eq!(1, evaluation.matches.len());
+        assert_eq!(
+            (1, 1),
+            (
+                evaluation.matches[0].predicted,
+                evaluation.matches[0].reference
+            )
+        );
+        assert!((evaluation.matches[0].iou - 1f64).abs() < 1e-9);
+        assert!(evaluation.matches[0].normal_error < 1e-6);
+        assert!((evaluation.precision() - 1f64 / 3f64).abs() < 1e-9);
+        assert!((evaluation.recall() - 0.5).abs() < 1e-9);
+        // the overlap is measured along the slope of the roof
+        assert!(
+            (iou(
+                &predicted[0],
+                &reference[0],
+                &reference[0].normal().unwrap()
+            ) - 0.4)
+                .abs()
+                < 0.01
+        );
+    }
+
+    #[test]
+    fn buildings_are_summarised_together() {
+        let square = |x: f64| path(&[[x, 0., 3.], [x + 1., 0., 3.], [x + 1., 1., 3.], [x, 1., 3.]]);
+        let predicted = IndexMap::from([("a".to_string(), vec![square(0.)])]);
+        let reference = IndexMap::from([
+            ("a".to_string(), vec![square(0.)]),
+            ("b".to_string(), vec![square(5.)]),
+        ]);
+        let evaluation = evaluate_groups(&predicted, &reference, &Tolerances::default());
+        let total = evaluation.total();
+
+        assert_eq!(2, evaluation.buildings.len());
+        assert_eq!(1f64, evaluation.buildings["b"].precision());
+        assert_eq!(
+            (1, 2, 1),
+            (total.predicted, total.references, total.matches.len())
+        );
+        assert_eq!(0.5, total.recall());
+        assert_eq!(1f64, evaluation.summary()["dataset"]["precision"]);
+    }
+}
@@ -0,0 +1,48 @@
+//! Geometries shared by the unit tests.
+
+use super::coordinates::Coordinates;
+use super::path::Path;
+
+/// Line going from `from` to `to`.
+pub fn line(from: [f64; 3], to: [f64; 3]) -> (Coordinates, Coordinates) {
+    (
+        Coordinates {
+            x: from[0],
+            y: from[1],
+            z: from[2],
+        },
+        Coordinates {
+            x: to[0],
+            y: to[1],
+            z: to[2],
+        },
+    )
+}
+
+/// Path along the unclosed `ring`.
+pub fn path(ring: &[[f64; 3]]) -> Path {
+    Path::from(
+        &ring
+            .iter()
+            .map(|[x, y, z]| Coordinates {
+                x: *x,
+                y: *y,
+                z: *z,
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Lines of a gable roof 10 m long along x and 8 m deep, its eaves at 5 m and its ridge at 8 m,
+/// starting with the southern eave, the ridge being the fourth line.
+pub fn gable() -> Vec<(Coordinates, Coordinates)> {
+    vec![
+        line([0.0, 0.0, 5.0], [10.0, 0.0, 5.0]),
+        line([0.0, 0.0, 5.0], [0.0, 4.0, 8.0]),
+        line([10.0, 0.0, 5.0], [10.0, 4.0, 8.0]),
+        line([0.0, 4.0, 8.0], [10.0, 4.0, 8.0]),
+        line([0.0, 4.0, 8.0], [0.0, 8.0, 5.0]),
+        line([10.0, 4.0, 8.0], [10.0, 8.0, 5.0]),
+        line([0.0, 8.0, 5.0], [10.0, 8.0, 5.0]),
+    ]
+}
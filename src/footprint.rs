@@ -0,0 +1,476 @@
+use rstar::primitives::GeomWithData;
+use rstar::{PointDistance, RTree};
+use std::collections::HashMap;
+
+use super::coordinates::Coordinates;
+use super::io::LineKind;
+use super::polygon::Polygon;
+use super::topology::RoofTopology;
+
+/// Distance, in metres, below which points are considered the same in the XY plane.
+const EPSILON: f64 = 1e-6;
+
+/// Vertices in the XY plane, each one holding its index.
+type Vertices = RTree<GeomWithData<[f64; 2], usize>>;
+
+/// Outline of a building on the ground, as the union of its roof faces projected onto the XY
+/// plane.
+#[derive(Debug, Clone, Default)]
+pub struct Footprint {
+    /// Polygons of the footprint, each one made of its closed exterior ring, counterclockwise,
+    /// followed by its closed holes, clockwise, all at zero height.
+    pub polygons: Vec<Vec<Vec<Coordinates>>>,
+}
+
+/// Region of the XY plane bounded by closed rings, counterclockwise around filled parts and
+/// clockwise around holes, with the bounding box of all of them.
+struct Region {
+    rings: Vec<Vec<Coordinates>>,
+    boundary: (Coordinates, Coordinates),
+}
+
+impl Footprint {
+    /// Unions the roof faces formed by `polygons` projected onto the XY plane, faces seen edge-on
+    /// such as walls being ignored.
+    pub fn of(polygons: &[Polygon<'_>]) -> Self {
+        let regions = polygons
+            .iter()
+            .filter_map(|polygon| Region::from(flattened(&polygon.path.sequence)))
+            .collect::<Vec<_>>();
+
+        Self {
+            polygons: Footprint::assemble(union(&regions)),
+        }
+    }
+
+    /// Area of the footprint.
+    pub fn area(&self) -> f64 {
+        self.polygons
+            .iter()
+            .flatten()
+            .map(|ring| Polygon::signed_area(ring))
+            .sum()
+    }
+
+    /// Area of the footprint lying outside the building outline formed by the `Bygningslinje`
+    /// lines among `lines`, namely the area of the roof overhanging the walls.
+    ///
+    /// Yields nothing when no closed building outline can be found among the lines.
+    pub fn overhang(
+        &self,
+        lines: &[(Coordinates, Coordinates)],
+        kinds: &HashMap<(Coordinates, Coordinates), LineKind>,
+    ) -> Option<f64> {
+        let building = lines
+            .iter()
+            .filter(|line| kinds.get(line) == Some(&LineKind::Building))
+            .map(|(u, v)| (flat(u), flat(v)))
+            .collect::<Vec<_>>();
+        let outline = Region::outline(RoofTopology::loops(&building))?;
+        let footprint = Region::oriented(self.polygons.iter().flatten().cloned().collect());
+        let outline_area = outline
+            .rings
+            .iter()
+            .map(|ring| Polygon::signed_area(ring))
+            .sum::<f64>();
+        let regions = footprint.into_iter().chain([outline]).collect::<Vec<_>>();
+        let union_area = union(&regions)
+            .iter()
+            .map(|ring| Polygon::signed_area(ring))
+            .sum::<f64>();
+
+        Some((union_area - outline_area).max(0f64))
+    }
+
+    /// Groups closed rings into polygons, each hole going to the smallest exterior containing it.
+    fn assemble(rings: Vec<Vec<Coordinates>>) -> Vec<Vec<Vec<Coordinates>>> {
+        let (mut exteriors, holes): (Vec<_>, Vec<_>) = rings
+            .into_iter()
+            .partition(|ring| Polygon::signed_area(ring) > 0f64);
+
+        exteriors.sort_by(|a, b| Polygon::signed_area(a).total_cmp(&Polygon::signed_area(b)));
+
+        let mut polygons = exteriors
+            .into_iter()
+            .map(|exterior| vec![exterior])
+            .collect::<Vec<_>>();
+
+        for hole in holes {
+            let point = midpoint(&hole[0], &hole[1]);
+
+            if let Some(polygon) = polygons
+                .iter_mut()
+                .find(|polygon| winding(&polygon[0], &point) != 0)
+            {
+                polygon.push(hole);
+            }
+        }
+
+        polygons
+    }
+}
+
+impl Region {
+    /// Filled region bounded by `ring`, whatever its orientation, unless it encloses no area.
+    fn from(ring: Vec<Coordinates>) -> Option<Self> {
+        let area = Polygon::signed_area(&ring);
+
+        if area.abs() <= EPSILON * EPSILON {
+            None
+        } else if area < 0f64 {
+            Region::oriented(vec![ring.into_iter().rev().collect()])
+        } else {
+            Region::oriented(vec![ring])
+        }
+    }
+
+    /// Region bounded by the loops of a building outline, loops nested in an odd number of other
+    /// ones being holes such as courtyards.
+    fn outline(loops: Vec<Vec<Coordinates>>) -> Option<Self> {
+        let rings = loops
+            .iter()
+            .map(|ring| {
+                let point = midpoint(&ring[0], &ring[1]);
+                let depth = loops
+                    .iter()
+                    .filter(|other| *other != ring && winding(other, &point) != 0)
+                    .count();
+
+                if (depth % 2 == 0) == (Polygon::signed_area(ring) > 0f64) {
+                    ring.clone()
+                } else {
+                    ring.iter().rev().copied().collect()
+                }
+            })
+            .collect();
+
+        Region::oriented(rings)
+    }
+
+    fn oriented(rings: Vec<Vec<Coordinates>>) -> Option<Self> {
+        if rings.is_empty() {
+            return None;
+        }
+
+        let boundary = Polygon::boundary(&rings.concat());
+
+        Some(Self { rings, boundary })
+    }
+
+    /// Whether the bounding boxes of both regions overlap or touch.
+    fn meets(&self, other: &Self) -> bool {
+        self.boundary.0.x <= other.boundary.1.x + EPSILON
+            && other.boundary.0.x <= self.boundary.1.x + EPSILON
+            && self.boundary.0.y <= other.boundary.1.y + EPSILON
+            && other.boundary.0.y <= self.boundary.1.y + EPSILON
+    }
+
+    /// Sides of the rings of the region.
+    fn sides(&self) -> impl Iterator<Item = (Coordinates, Coordinates)> + '_ {
+        self.rings
+            .iter()
+            .flat_map(|ring| ring.windows(2).map(|side| (side[0], side[1])))
+    }
+}
+
+/// Rings bounding the union of `regions`, counterclockwise around filled parts and clockwise
+/// around holes.
+///
+/// Sides of every region are split where they meet sides of other regions, and the parts
+/// within another region or shared with a region lying on their other side are dropped. The
+/// remaining parts are then chained into rings.
+fn union(regions: &[Region]) -> Vec<Vec<Coordinates>> {
+    let mut vertices = Vec::<Coordinates>::new();
+    let mut nearest = Vertices::new();
+    let mut kept = Vec::<(usize, usize)>::new();
+
+    for (index, region) in regions.iter().enumerate() {
+        let others = regions
+            .iter()
+            .enumerate()
+            .filter(|(other, candidate)| *other != index && region.meets(candidate))
+            .collect::<Vec<_>>();
+
+        for (a, b) in region.sides() {
+            let mut splits = vec![0f64, 1f64];
+
+            for (_, other) in &others {
+                for (c, d) in other.sides() {
+                    splits.extend(intersections(&a, &b, &c, &d));
+                }
+            }
+
+            splits.sort_by(f64::total_cmp);
+            splits.dedup_by(|t, s| (*t - *s).abs() * distance(&a, &b) <= EPSILON);
+
+            for part in splits.windows(2) {
+                let (p, q) = (interpolated(&a, &b, part[0]), interpolated(&a, &b, part[1]));
+                let m = midpoint(&p, &q);
+                let keep = others.iter().all(|(other, candidate)| {
+                    match candidate
+                        .sides()
+                        .find(|(c, d)| distance_to_side(&m, c, d) <= EPSILON)
+                    {
+                        // parts shared by regions lying on the same side are kept once
+                        Some((c, d)) => same_direction(&p, &q, &c, &d) && index < *other,
+                        None => {
+                            candidate
+                                .rings
+                                .iter()
+                                .map(|ring| winding(ring, &m))
+                                .sum::<i32>()
+                                == 0
+                        }
+                    }
+                });
+
+                if keep {
+                    let from = vertex(&mut vertices, &mut nearest, p);
+                    let to = vertex(&mut vertices, &mut nearest, q);
+
+                    if from != to {
+                        kept.push((from, to));
+                    }
+                }
+            }
+        }
+    }
+
+    chained(&vertices, kept)
+}
+
+/// Chains directed sides, given as indices into `vertices`, into closed rings.
+fn chained(vertices: &[Coordinates], sides: Vec<(usize, usize)>) -> Vec<Vec<Coordinates>> {
+    let mut successors = HashMap::<usize, Vec<usize>>::new();
+
+    for (from, to) in &sides {
+        successors.entry(*from).or_default().push(*to);
+    }
+
+    let mut rings = Vec::<Vec<Coordinates>>::new();
+
+    for (start, _) in sides {
+        let mut ring = vec![start];
+        let mut current = start;
+
+        while let Some(next) = successors.get_mut(&current).and_then(|to| to.pop()) {
+            ring.push(next);
+            current = next;
+
+            if current == start {
+                break;
+            }
+        }
+
+        if ring.len() > 3 && ring.first() == ring.last() {
+            rings.push(ring.iter().map(|index| vertices[*index]).collect());
+        }
+    }
+
+    rings
+}
+
+/// Index of `point` among `vertices`, added unless a vertex lies within tolerance, as found
+/// through `nearest` which indexes the same vertices.
+fn vertex(vertices: &mut Vec<Coordinates>, nearest: &mut Vertices, point: Coordinates) -> usize {
+    let location = [point.x, point.y];
+
+    match nearest.nearest_neighbor(&location) {
+        Some(vertex) if vertex.distance_2(&location) <= EPSILON * EPSILON => vertex.data,
+        _ => {
+            vertices.push(point);
+            nearest.insert(GeomWithData::new(location, vertices.len() - 1));
+            vertices.len() - 1
+        }
+    }
+}
+
+/// Positions along side `ab`, as fractions of its length, where side `cd` meets it.
+fn intersections(a: &Coordinates, b: &Coordinates, c: &Coordinates, d: &Coordinates) -> Vec<f64> {
+    let r = (b.x - a.x, b.y - a.y);
+    let s = (d.x - c.x, d.y - c.y);
+    let length = distance(a, b);
+    let denominator = r.0 * s.1 - r.1 * s.0;
+    let along =
+        |point: &Coordinates| ((point.x - a.x) * r.0 + (point.y - a.y) * r.1) / (length * length);
+    let inner = |t: f64| t * length > EPSILON && (1f64 - t) * length > EPSILON;
+
+    if length <= EPSILON {
+        return vec![];
+    }
+
+    if denominator.abs() > EPSILON * length * distance(c, d) {
+        let t = ((c.x - a.x) * s.1 - (c.y - a.y) * s.0) / denominator;
+        let u = ((c.x - a.x) * r.1 - (c.y - a.y) * r.0) / denominator;
+        let span = EPSILON / distance(c, d);
+
+        if inner(t) && (-span..=1f64 + span).contains(&u) {
+            vec![t]
+        } else {
+            vec![]
+        }
+    } else {
+        // collinear sides overlapping split each other at their extremities
+        [c, d]
+            .into_iter()
+            .filter(|point| distance_to_side(point, a, b) <= EPSILON)
+            .map(along)
+            .filter(|t| inner(*t))
+            .collect()
+    }
+}
+
+/// Winding number of `ring` around `point`, positive for counterclockwise rings.
+fn winding(ring: &[Coordinates], point: &Coordinates) -> i32 {
+    ring.windows(2)
+        .map(|side| {
+            let (a, b) = (side[0], side[1]);
+            let cross = (b.x - a.x) * (point.y - a.y) - (point.x - a.x) * (b.y - a.y);
+
+            if a.y <= point.y && b.y > point.y && cross > 0f64 {
+                1
+            } else if a.y > point.y && b.y <= point.y && cross < 0f64 {
+                -1
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+fn same_direction(p: &Coordinates, q: &Coordinates, c: &Coordinates, d: &Coordinates) -> bool {
+    (q.x - p.x) * (d.x - c.x) + (q.y - p.y) * (d.y - c.y) > 0f64
+}
+
+fn distance_to_side(point: &Coordinates, a: &Coordinates, b: &Coordinates) -> f64 {
+    let length = distance(a, b);
+
+    if length <= EPSILON {
+        return distance(point, a);
+    }
+
+    let t = (((point.x - a.x) * (b.x - a.x) + (point.y - a.y) * (b.y - a.y)) / (length * length))
+        .clamp(0f64, 1f64);
+
+    distance(point, &interpolated(a, b, t))
+}
+
+fn distance(a: &Coordinates, b: &Coordinates) -> f64 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+fn interpolated(a: &Coordinates, b: &Coordinates, t: f64) -> Coordinates {
+    Coordinates {
+        x: a.x + t * (b.x - a.x),
+        y: a.y + t * (b.y - a.y),
+        z: 0f64,
+    }
+}
+
+fn midpoint(a: &Coordinates, b: &Coordinates) -> Coordinates {
+    interpolated(a, b, 0.5f64)
+}
+
+fn flat(coordinates: &Coordinates) -> Coordinates {
+    Coordinates {
+        z: 0f64,
+        ..*coordinates
+    }
+}
+
+fn flattened(ring: &[Coordinates]) -> Vec<Coordinates> {
+    ring.iter().map(flat).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::path;
+    use crate::path::Path;
+
+    fn rectangle(min: [f64; 2], max: [f64; 2], z: f64) -> Path {
+        path(&[
+            [min[0], min[1], z],
+            [max[0], min[1], z],
+            [max[0], max[1], z],
+            [min[0], max[1], z],
+        ])
+    }
+
+    fn footprint(paths: &[Path]) -> Footprint {
+        Footprint::of(&paths.iter().map(Polygon::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn faces_are_merged() {
+        let gable = footprint(&[
+            path(&[
+                [0.0, 0.0, 5.0],
+                [10.0, 0.0, 5.0],
+                [10.0, 4.0, 8.0],
+                [0.0, 4.0, 8.0],
+            ]),
+            path(&[
+                [0.0, 4.0, 8.0],
+                [10.0, 4.0, 8.0],
+                [10.0, 8.0, 5.0],
+                [0.0, 8.0, 5.0],
+            ]),
+        ]);
+
+        assert_eq!(1, gable.polygons.len());
+        assert_eq!(1, gable.polygons[0].len());
+        assert!((gable.area() - 80f64).abs() < 1e-9);
+        // an upper roof overhanging a lower one
+        let step = footprint(&[
+            rectangle([0.0, 0.0], [10.0, 10.0], 5.0),
+            rectangle([5.0, 0.0], [15.0, 10.0], 3.0),
+        ]);
+
+        assert_eq!(1, step.polygons.len());
+        assert!((step.area() - 150f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn courtyards_are_holes() {
+        let courtyard = footprint(&[
+            rectangle([0.0, 0.0], [10.0, 2.0], 5.0),
+            rectangle([0.0, 8.0], [10.0, 10.0], 5.0),
+            rectangle([0.0, 2.0], [2.0, 8.0], 5.0),
+            rectangle([8.0, 2.0], [10.0, 8.0], 5.0),
+        ]);
+
+        assert_eq!(1, courtyard.polygons.len());
+        assert_eq!(2, courtyard.polygons[0].len());
+        assert!(Polygon::signed_area(&courtyard.polygons[0][1]) < 0f64);
+        assert!((courtyard.area() - 64f64).abs() < 1e-9);
+        // walls around a smaller courtyard leave no roof outside them
+        let lines = [
+            rectangle([0.0, 0.0], [10.0, 10.0], 0.0),
+            rectangle([3.0, 3.0], [7.0, 7.0], 0.0),
+        ]
+        .iter()
+        .flat_map(|walls| walls.sides().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+        let kinds = lines
+            .iter()
+            .map(|line| (*line, LineKind::Building))
+            .collect::<HashMap<_, _>>();
+
+        assert!(courtyard.overhang(&lines, &kinds).unwrap().abs() < 1e-9);
+    }
+
+    #[test]
+    fn overhang_against_building_lines() {
+        let roof = footprint(&[rectangle([0.0, 0.0], [10.0, 8.0], 5.0)]);
+        let walls = rectangle([0.5, 0.5], [9.5, 7.5], 0.0);
+        let lines = walls.sides().collect::<Vec<_>>();
+        let kinds = lines
+            .iter()
+            .map(|line| (*line, LineKind::Building))
+            .collect::<HashMap<_, _>>();
+
+        assert!((roof.overhang(&lines, &kinds).unwrap() - 17f64).abs() < 1e-9);
+        assert_eq!(None, roof.overhang(&lines, &HashMap::new()));
+    }
+}
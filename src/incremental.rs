@@ -0,0 +1,210 @@
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+
+use super::coordinates::Coordinates;
+use super::path::Path;
+use super::pipeline::polygonalize;
+use super::tolerances::Tolerances;
+
+/// Polygons added and removed by an edit of the lines.
+#[derive(Clone, Default)]
+pub struct PolygonDiff {
+    pub added: Vec<Path>,
+    pub removed: Vec<Path>,
+}
+
+/// Keeps the polygons formed by a set of lines up to date while lines are inserted and removed,
+/// as when roof lines are fixed interactively.
+///
+/// Polygons never span several connected components of lines, so an edit only searches again
+/// the components its lines belong to, the polygons of all other components being kept as they
+/// are.
+pub struct IncrementalPolygonalizer {
+    tolerances: Vec<Tolerances>,
+    adjacencies: IndexMap<Coordinates, IndexSet<Coordinates>>,
+    faces: IndexSet<Path>,
+}
+
+impl PolygonDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl IncrementalPolygonalizer {
+    /// Polygonalizer without any line, searching polygons with each of the `tolerances` as
+    /// [`polygonalize`] does.
+    pub fn new(tolerances: &[Tolerances]) -> Self {
+        Self {
+            tolerances: tolerances.to_vec(),
+            adjacencies: IndexMap::new(),
+            faces: IndexSet::new(),
+        }
+    }
+
+    /// Polygonalizer starting from the polygons formed by `lines`.
+    pub fn from(lines: &[(Coordinates, Coordinates)], tolerances: &[Tolerances]) -> Self {
+        let mut polygonalizer = Self::new(tolerances);
+
+        polygonalizer.insert(lines);
+        polygonalizer
+    }
+
+    /// Polygons currently formed by the lines.
+    pub fn faces(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.faces.iter()
+    }
+
+    /// Lines currently held, each one once whatever the direction it was inserted in.
+    pub fn lines(&self) -> Vec<(Coordinates, Coordinates)> {
+        self.adjacencies
+            .iter()
+            .flat_map(|(u, to)| to.iter().filter(move |v| u < *v).map(move |v| (*u, *v)))
+            .collect()
+    }
+
+    /// Inserts `lines`, yielding the polygons this adds and removes.
+    pub fn insert(&mut self, lines: &[(Coordinates, Coordinates)]) -> PolygonDiff {
+        for (u, v) in lines.iter().filter(|(u, v)| u != v) {
+            self.adjacencies.entry(*u).or_default().insert(*v);
+            self.adjacencies.entry(*v).or_default().insert(*u);
+        }
+
+        self.update(lines)
+    }
+
+    /// Removes `lines`, whatever their direction, yielding the polygons this adds and removes.
+    pub fn remove(&mut self, lines: &[(Coordinates, Coordinates)]) -> PolygonDiff {
+        for (u, v) in lines {
+            for (from, to) in [(u, v), (v, u)] {
+                if let Some(adjacent) = self.adjacencies.get_mut(from) {
+                    adjacent.swap_remove(to);
+
+                    if adjacent.is_empty() {
+                        self.adjacencies.swap_remove(from);
+                    }
+                }
+            }
+        }
+
+        self.update(lines)
+    }
+
+    /// Searches again the polygons of the components the extremities of the `edited` lines now
+    /// belong to, replacing the polygons previously touching them.
+    fn update(&mut self, edited: &[(Coordinates, Coordinates)]) -> PolygonDiff {
+        let component = self.component(edited.iter().flat_map(|(u, v)| [*u, *v]));
+        // a removed line may leave its extremities out of any component
+        let touched = component
+            .iter()
+            .copied()
+            .chain(edited.iter().flat_map(|(u, v)| [*u, *v]))
+            .collect::<IndexSet<_>>();
+        let lines = component
+            .iter()
+            .flat_map(|u| {
+                self.adjacencies[u]
+                    .iter()
+                    .filter(move |v| u < *v)
+                    .map(move |v| (*u, *v))
+            })
+            .collect::<Vec<_>>();
+        let invalidated = self
+            .faces
+            .iter()
+            .filter(|face| face.sequence.iter().any(|vertex| touched.contains(vertex)))
+            .cloned()
+            .collect::<IndexSet<_>>();
+        let recomputed = polygonalize(&lines, &self.tolerances)
+            .into_iter()
+            .collect::<IndexSet<_>>();
+        let diff = PolygonDiff {
+            added: recomputed.difference(&invalidated).cloned().collect(),
+            removed: invalidated.difference(&recomputed).cloned().collect(),
+        };
+
+        for face in &diff.removed {
+            self.faces.swap_remove(face);
+        }
+
+        self.faces.extend(diff.added.iter().cloned());
+
+        diff
+    }
+
+    /// Vertices connected by lines to any of the `seeds`.
+    fn component(&self, seeds: impl Iterator<Item = Coordinates>) -> IndexSet<Coordinates> {
+        let mut component = IndexSet::<Coordinates>::new();
+        let mut pending = seeds
+            .filter(|seed| self.adjacencies.contains_key(seed))
+            .collect::<Vec<_>>();
+
+        while let Some(vertex) = pending.pop() {
+            if component.insert(vertex) {
+                pending.extend(
+                    self.adjacencies[&vertex]
+                        .iter()
+                        .filter(|adjacent| !component.contains(*adjacent)),
+                );
+            }
+        }
+
+        component
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{gable, line};
+    use crate::pipeline::TOLERANCES;
+
+    fn identifiers<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Vec<String> {
+        let mut identifiers = paths
+            .into_iter()
+            .map(|path| path.identifier(""))
+            .collect::<Vec<_>>();
+
+        identifiers.sort();
+        identifiers
+    }
+
+    #[test]
+    fn edits_only_change_affected_faces() {
+        let gable = gable();
+        // a flat roof standing apart
+        let flat = vec![
+            line([20.0, 0.0, 3.0], [30.0, 0.0, 3.0]),
+            line([30.0, 0.0, 3.0], [30.0, 8.0, 3.0]),
+            line([30.0, 8.0, 3.0], [20.0, 8.0, 3.0]),
+            line([20.0, 8.0, 3.0], [20.0, 0.0, 3.0]),
+        ];
+        let mut polygonalizer =
+            IncrementalPolygonalizer::from(&[&gable[1..], &flat[..]].concat(), &TOLERANCES);
+
+        assert_eq!(2, polygonalizer.faces().count());
+        // closing the open face of the gable adds it alone
+        let diff = polygonalizer.insert(&gable[..1]);
+
+        assert_eq!(1, diff.added.len());
+        assert!(diff.removed.is_empty());
+        assert_eq!(3, polygonalizer.faces().count());
+        // removing the ridge opens both faces of the gable but not the flat roof
+        let diff = polygonalizer.remove(&[(gable[3].1, gable[3].0)]);
+
+        assert!(diff.added.is_empty());
+        assert_eq!(2, diff.removed.len());
+        assert_eq!(
+            identifiers(polygonalize(&flat, &TOLERANCES).iter()),
+            identifiers(polygonalizer.faces())
+        );
+        // inserting it back restores the faces of a full run
+        polygonalizer.insert(&gable[3..4]);
+
+        assert_eq!(
+            identifiers(polygonalize(&polygonalizer.lines(), &TOLERANCES).iter()),
+            identifiers(polygonalizer.faces())
+        );
+        assert!(polygonalizer.insert(&gable[3..4]).is_empty());
+    }
+}
@@ -1,23 +1,52 @@
+use indexmap::IndexMap;
 use indexmap::IndexSet;
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde_json::json;
+use serde_json::Map;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
 use std::io;
+use std::io::BufRead;
 use std::io::Write;
 
+use super::aggregation::PropertyRule;
+use super::classification::RoofClassification;
 use super::coordinates::Coordinates;
+use super::curved::CurvedSurface;
+use super::diagnostics::Diagnostics;
+use super::footprint::Footprint;
 use super::path::Path;
+use super::pathgraph::PathGraphBuilder;
+use super::pipeline::polygonalize_repaired;
 use super::polygon::Polygon;
+use super::solid::{Ground, Solid, SurfaceKind};
+use super::tolerances::Tolerances;
+use super::topology::RoofTopology;
+
+/// Routine extracting the polygons of a building from its lines, together with the lines it
+/// inferred as missing.
+type Polygonalization<'a> = &'a mut dyn FnMut(
+    &Vec<(Coordinates, Coordinates)>,
+) -> (Vec<Path>, Vec<(Coordinates, Coordinates)>);
+/// Properties of each input line, in the order the lines were read.
+type LineProperties = IndexMap<(Coordinates, Coordinates), Map<String, Value>>;
 
 /// Different kind of input lines from the expected dataset.
-#[derive(Debug, Clone, Copy)]
-enum LineKind {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineKind {
+    /// Ridge line (`Mønelinje`) where two roof faces meet.
     Ridge,
+    /// Roof edge (`Takkant`), such as eaves and verges, bounding a single roof face.
     Edge,
+    /// Upper line of a roof step (`Taksprang`).
     RoofGap,
+    /// Lower line of a roof step (`TaksprangBunn`).
     RoofGapLine,
+    /// Building outline (`Bygningslinje`) where walls meet the ground.
     Building,
+    /// Helping line (`Hjelpelinje3D`) splitting or closing roof faces.
     Helping,
 }
 
@@ -29,6 +58,18 @@ pub struct GeoJson {
     metadata: Value,
     /// Saved line kinds to be re-exported when producing an output file.
     linekinds: HashMap<(Coordinates, Coordinates), LineKind>,
+    /// Saved line properties to be propagated to the output polygons.
+    properties: LineProperties,
+    /// Rules propagating line properties to the output polygons.
+    rules: Vec<PropertyRule>,
+    /// Distances line endpoints are snapped within while extracting the output polygons.
+    snapping: Vec<f64>,
+    /// Name of the property lines were grouped by, if any.
+    grouping: Option<String>,
+    /// Original values of the grouping property, to be re-exported when producing an output file.
+    groups: HashMap<String, Value>,
+    /// Lines inferred as missing from the input, marking the output polygons they bound.
+    synthetic: HashSet<(Coordinates, Coordinates)>,
 }
 
 impl GeoJson {
@@ -41,11 +82,22 @@ impl GeoJson {
                 ),
                 metadata: serde_json::from_str(&content).unwrap(),
                 linekinds: HashMap::new(),
+                properties: IndexMap::new(),
+                rules: Vec::new(),
+                snapping: Vec::new(),
+                grouping: None,
+                groups: HashMap::new(),
+                synthetic: HashSet::new(),
             },
             Err(_) => panic!("Unable to read file `{}`", filename),
         }
     }
 
+    /// Kinds of the lines read so far, keyed by the line as digitised.
+    pub fn kinds(&self) -> &HashMap<(Coordinates, Coordinates), LineKind> {
+        &self.linekinds
+    }
+
     /// Parse an input geojson dataset into the list of lines it contains.
     pub fn parse(&mut self) -> Vec<(Coordinates, Coordinates)> {
         // all lines contained in the file as pair of coordinates
@@ -53,81 +105,637 @@ impl GeoJson {
         // each one is added and its kind is stored for future retrieval
         for element in self.metadata["features"].as_array().unwrap() {
             // skip the element if not a line
-            if &element["geometry"]["type"] != "LineString" {
-                continue;
-            }
-            // extreme coordinates of the line
-            let coordinates = element["geometry"]["coordinates"].as_array().unwrap();
-            // unpacks them
-            let from = coordinates[0].as_array().unwrap();
-            let to = coordinates[1].as_array().unwrap();
-            // converts to points
-            let line = (
-                Coordinates {
-                    x: from[0].as_f64().unwrap(),
-                    y: from[1].as_f64().unwrap(),
-                    z: from[2].as_f64().unwrap(),
-                },
-                Coordinates {
-                    x: to[0].as_f64().unwrap(),
-                    y: to[1].as_f64().unwrap(),
-                    z: to[2].as_f64().unwrap(),
-                },
-            );
-            // matches the line against different kinds
-            match element["properties"]["type"].as_str() {
-                Some("Takkant") => {
-                    self.linekinds.insert(line, LineKind::Edge);
-                }
-                Some("Mønelinje") => {
-                    self.linekinds.insert(line, LineKind::Ridge);
+            if let Some(line) = GeoJson::line(element) {
+                // stores its kind if known
+                if let Some(kind) = GeoJson::kind(element) {
+                    self.linekinds.insert(line, kind);
                 }
-                Some("Taksprang") => {
-                    self.linekinds.insert(line, LineKind::RoofGap);
+                // stores its properties for propagation
+                if let Some(properties) = element["properties"].as_object() {
+                    self.properties.insert(line, properties.clone());
                 }
-                Some("TaksprangBunn") => {
-                    self.linekinds.insert(line, LineKind::RoofGapLine);
+                // adds line
+                lines.push(line);
+            }
+        }
+        // yields the list of lines that can be used to build the path graph
+        lines
+    }
+
+    /// Sets the `rules` propagating the properties of the input lines to the output polygons they
+    /// bound, lines being related to the sides of the polygons through their endpoints as snapped
+    /// with each of the `tolerances` the polygons are extracted with.
+    pub fn propagate(&mut self, rules: &[PropertyRule], tolerances: &[Tolerances]) {
+        self.rules = rules.to_vec();
+        self.snapping = GeoJson::snapping(tolerances);
+    }
+
+    /// Records the `lines` inferred as missing when repairing the input, such as the synthetic
+    /// lines of [`Diagnostics`], so that the output polygons they bound are marked as synthetic.
+    pub fn synthesize(&mut self, lines: &[(Coordinates, Coordinates)]) {
+        self.synthetic
+            .extend(lines.iter().map(|(u, v)| Diagnostics::undirected(u, v)));
+    }
+
+    /// Whether `path` is bounded by one of the `synthetic` lines, as recorded by
+    /// [`GeoJson::synthesize`].
+    fn is_synthetic(synthetic: &HashSet<(Coordinates, Coordinates)>, path: &Path) -> bool {
+        !synthetic.is_empty()
+            && path
+                .sides()
+                .any(|(u, v)| synthetic.contains(&Diagnostics::undirected(&u, &v)))
+    }
+
+    /// Parse an input geojson dataset into the lines it contains grouped by the value of `property`,
+    /// such as a building identifier, in order of first appearance.
+    pub fn parse_groups(
+        &mut self,
+        property: &str,
+    ) -> IndexMap<String, Vec<(Coordinates, Coordinates)>> {
+        // lines of each group
+        let mut groups = IndexMap::<String, Vec<(Coordinates, Coordinates)>>::new();
+        // each one is added and its kind is stored for future retrieval
+        for element in self.metadata["features"].as_array().unwrap() {
+            // skip the element if not a line
+            if let Some(line) = GeoJson::line(element) {
+                // stores its kind if known
+                if let Some(kind) = GeoJson::kind(element) {
+                    self.linekinds.insert(line, kind);
                 }
-                Some("Bygningslinje") => {
-                    self.linekinds.insert(line, LineKind::Building);
+                // stores its properties for propagation
+                if let Some(properties) = element["properties"].as_object() {
+                    self.properties.insert(line, properties.clone());
                 }
-                Some("Hjelpelinje3D") => {
-                    self.linekinds.insert(line, LineKind::Helping);
+                // lines lacking the property are gathered in the `null` group
+                let value = &element["properties"][property];
+                let group = GeoJson::group(value);
+                // stores the original value for future retrieval
+                self.groups.entry(group.clone()).or_insert(value.clone());
+                // adds line to its group
+                groups.entry(group).or_default().push(line);
+            }
+        }
+        // saves the property to write it back
+        self.grouping = Some(property.to_string());
+        // yields the lines of each group, each one used to build its own path graph
+        groups
+    }
+
+    /// Parse an input geojson dataset of polygons, such as reference roof faces or the output of
+    /// [`GeoJson::save_groups`], into the exterior rings they contain grouped by the value of
+    /// `property`, in order of first appearance.
+    pub fn parse_faces(&mut self, property: &str) -> IndexMap<String, Vec<Path>> {
+        // faces of each group
+        let mut groups = IndexMap::<String, Vec<Path>>::new();
+
+        for element in self.metadata["features"].as_array().unwrap() {
+            // skip the element if not a polygon
+            if let Some(face) = GeoJson::face(element) {
+                // faces lacking the property are gathered in the `null` group
+                let value = &element["properties"][property];
+                let group = GeoJson::group(value);
+
+                self.groups.entry(group.clone()).or_insert(value.clone());
+                groups.entry(group).or_default().push(face);
+            }
+        }
+
+        self.grouping = Some(property.to_string());
+
+        groups
+    }
+
+    /// Converts a value of the grouping property into a group identifier, strings being kept as is
+    /// unless they read as json, such as `"1"`, which are quoted to tell them apart from the value
+    /// they read as, such as the number `1`.
+    fn group(value: &Value) -> String {
+        match value.as_str() {
+            Some(group) if serde_json::from_str::<Value>(group).is_err() => group.to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// Extracts the line described by a geojson feature `element`, if it is a line.
+    fn line(element: &Value) -> Option<(Coordinates, Coordinates)> {
+        // skip the element if not a line
+        if &element["geometry"]["type"] != "LineString" {
+            return None;
+        }
+        // extreme coordinates of the line
+        let coordinates = element["geometry"]["coordinates"].as_array().unwrap();
+        // unpacks them
+        let from = coordinates[0].as_array().unwrap();
+        let to = coordinates[1].as_array().unwrap();
+        // converts to points
+        Some((
+            Coordinates {
+                x: from[0].as_f64().unwrap(),
+                y: from[1].as_f64().unwrap(),
+                z: from[2].as_f64().unwrap(),
+            },
+            Coordinates {
+                x: to[0].as_f64().unwrap(),
+                y: to[1].as_f64().unwrap(),
+                z: to[2].as_f64().unwrap(),
+            },
+        ))
+    }
+
+    /// Extracts the exterior ring of the polygon described by a geojson feature `element`, if it
+    /// is a polygon.
+    fn face(element: &Value) -> Option<Path> {
+        if &element["geometry"]["type"] != "Polygon" {
+            return None;
+        }
+
+        let ring = element["geometry"]["coordinates"][0]
+            .as_array()?
+            .iter()
+            .map(|position| {
+                let position = position.as_array().unwrap();
+
+                Coordinates {
+                    x: position[0].as_f64().unwrap(),
+                    y: position[1].as_f64().unwrap(),
+                    z: position[2].as_f64().unwrap(),
                 }
-                _ => (),
+            })
+            .collect::<Vec<_>>();
+        // rings are closed in geojson
+        Some(Path::from(&ring[..ring.len().saturating_sub(1)]))
+    }
+
+    /// Matches the `type` property of a geojson feature `element` against the different kinds of lines.
+    fn kind(element: &Value) -> Option<LineKind> {
+        match element["properties"]["type"].as_str() {
+            Some("Takkant") => Some(LineKind::Edge),
+            Some("Mønelinje") => Some(LineKind::Ridge),
+            Some("Taksprang") => Some(LineKind::RoofGap),
+            Some("TaksprangBunn") => Some(LineKind::RoofGapLine),
+            Some("Bygningslinje") => Some(LineKind::Building),
+            Some("Hjelpelinje3D") => Some(LineKind::Helping),
+            _ => None,
+        }
+    }
+
+    /// Distinct positive snapping distances among `tolerances`.
+    fn snapping(tolerances: &[Tolerances]) -> Vec<f64> {
+        let mut distances = Vec::<f64>::new();
+
+        for tolerances in tolerances {
+            if tolerances.snapping > 0f64 && !distances.contains(&tolerances.snapping) {
+                distances.push(tolerances.snapping);
             }
-            // adds line
-            lines.push(line);
         }
-        // yields the list of lines that can be used to build the path graph
-        lines
+
+        distances
+    }
+
+    /// Line `properties` keyed by the lines as read and as snapped within each of the `snapping`
+    /// distances, as they appear among the sides of the output polygons.
+    fn snapped<'a>(
+        properties: &'a LineProperties,
+        snapping: &[f64],
+    ) -> HashMap<(Coordinates, Coordinates), &'a Map<String, Value>> {
+        let mut snapped = properties
+            .iter()
+            .map(|(line, properties)| (*line, properties))
+            .collect::<HashMap<_, _>>();
+        let lines = properties.keys().copied().collect::<Vec<_>>();
+
+        for distance in snapping {
+            let vertices = PathGraphBuilder::snapped(&lines, *distance);
+            let snap =
+                |coordinates: &Coordinates| *vertices.get(coordinates).unwrap_or(coordinates);
+
+            for (line, properties) in properties {
+                snapped
+                    .entry((snap(&line.0), snap(&line.1)))
+                    .or_insert(properties);
+            }
+        }
+
+        snapped
+    }
+
+    /// Aggregates the `properties` of the lines forming the boundary of `path` following `rules`,
+    /// the lines being keyed as by [`GeoJson::snapped`].
+    fn propagated(
+        rules: &[PropertyRule],
+        properties: &HashMap<(Coordinates, Coordinates), &Map<String, Value>>,
+        path: &Path,
+    ) -> Map<String, Value> {
+        if rules.is_empty() {
+            return Map::new();
+        }
+        // lines may have been digitised in any direction
+        let boundary = path
+            .sides()
+            .filter_map(|(a, b)| properties.get(&(a, b)).or_else(|| properties.get(&(b, a))))
+            .copied()
+            .collect::<Vec<_>>();
+
+        PropertyRule::aggregate(rules, &boundary)
+    }
+
+    /// Classifies the roof formed by `paths`, walls left out, into building-level properties.
+    fn classified(
+        paths: &[Path],
+        kinds: &HashMap<(Coordinates, Coordinates), LineKind>,
+    ) -> Map<String, Value> {
+        let polygons = paths
+            .iter()
+            .filter(|path| !path.vertical)
+            .map(Polygon::from)
+            .collect::<Vec<_>>();
+        let classification = RoofClassification::of(&RoofTopology::from(&polygons, kinds));
+        let mut properties = Map::new();
+
+        properties.insert(
+            "roof_type".to_string(),
+            Value::String(classification.roof.name().to_string()),
+        );
+        properties.insert(
+            "roof_type_confidence".to_string(),
+            json!(classification.confidence),
+        );
+
+        properties
+    }
+
+    /// Kind of surface of the polygon given by `path`, as named in CityGML, polygons tagged as
+    /// vertical by the pipeline being walls and all others roofs.
+    fn surface(path: &Path) -> Value {
+        let kind = if path.vertical {
+            SurfaceKind::Wall
+        } else {
+            SurfaceKind::Roof
+        };
+
+        Value::String(kind.name().to_string())
     }
 
+    /// Builds the geojson feature of a polygon given its `path` and `properties`.
+    fn feature(path: &Path, properties: Value) -> Value {
+        json!({
+            "type": "Feature",
+            "properties": properties,
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [
+                    path.sequence
+                        .iter()
+                        .map(|coordinates| [ coordinates.x, coordinates.y, coordinates.z ])
+                        .collect::<Vec<_>>()
+                ]
+            }
+        })
+    }
+
+    /// Saves the `polygons` of a single, ungrouped roof.
+    ///
+    /// Roof types are classified per building and are therefore only written by
+    /// [`GeoJson::save_groups`], not here.
     pub fn save(&self, polygons: &Vec<Polygon<'_>>, directory: &str) {
+        let lines = GeoJson::snapped(&self.properties, &self.snapping);
         // creates the geojson features even considering invalid lines to have a full output
         let features = polygons
             .iter()
-            .enumerate()
-            .map(|(identifier, polygon)| {
+            .map(|polygon| {
+                let mut properties = GeoJson::propagated(&self.rules, &lines, polygon.path);
+
+                properties.insert(
+                    "label".to_string(),
+                    Value::String(polygon.path.identifier("")),
+                );
+                properties.insert("surface".to_string(), GeoJson::surface(polygon.path));
+
+                if GeoJson::is_synthetic(&self.synthetic, polygon.path) {
+                    properties.insert("synthetic".to_string(), Value::Bool(true));
+                }
+
+                GeoJson::feature(polygon.path, Value::Object(properties))
+            })
+            .collect::<Vec<Value>>();
+        // writes to an output file
+        self.write(features, directory);
+    }
+
+    /// Saves the polygons of each group, as returned by [`GeoJson::parse_groups`], writing the
+    /// value of the grouping property onto every polygon.
+    pub fn save_groups(&self, groups: &IndexMap<String, Vec<Path>>, directory: &str) {
+        // name of the property written back
+        let property = self.grouping.as_deref().unwrap_or("group");
+        let lines = GeoJson::snapped(&self.properties, &self.snapping);
+        // creates the geojson features labelled within their group
+        let features = groups
+            .iter()
+            .flat_map(|(group, paths)| {
+                // the roof type is a building-level attribute shared by all its polygons
+                let roof = GeoJson::classified(paths, &self.linekinds);
+
+                paths.iter().map(move |path| (group, path, roof.clone()))
+            })
+            .map(|(group, path, roof)| {
+                let mut properties = GeoJson::propagated(&self.rules, &lines, path);
+
+                properties.extend(roof);
+                properties.insert("label".to_string(), Value::String(path.identifier(group)));
+                properties.insert("surface".to_string(), GeoJson::surface(path));
+
+                if GeoJson::is_synthetic(&self.synthetic, path) {
+                    properties.insert("synthetic".to_string(), Value::Bool(true));
+                }
+                properties.insert(
+                    property.to_string(),
+                    self.groups
+                        .get(group)
+                        .cloned()
+                        .unwrap_or_else(|| Value::String(group.clone())),
+                );
+
+                GeoJson::feature(path, Value::Object(properties))
+            })
+            .collect::<Vec<Value>>();
+        // writes to an output file
+        self.write(features, directory);
+    }
+
+    /// Saves the buildings of each group as LoD2 solids closed down to the `ground`, in a CityJSON
+    /// file named after the input file with a `.city.json` extension and located within
+    /// `directory`.
+    ///
+    /// Buildings whose roof cannot be closed into a watertight solid are left out.
+    pub fn save_solids(
+        &self,
+        groups: &IndexMap<String, Vec<Path>>,
+        ground: &Ground,
+        directory: &str,
+    ) {
+        let mut solids = Vec::<(&String, Solid, Map<String, Value>)>::new();
+
+        for (group, paths) in groups {
+            let polygons = paths.iter().map(Polygon::from).collect::<Vec<_>>();
+            let height = self.ground(ground, paths);
+
+            if let Some(solid) = Solid::extrude(&polygons, height) {
+                if !solid.is_manifold() {
+                    continue;
+                }
+
+                let mut attributes = GeoJson::classified(paths, &self.linekinds);
+                attributes.insert("ground".to_string(), json!(height));
+
+                solids.push((group, solid, attributes));
+            }
+        }
+        // vertices are stored as integers in millimetres relative to the lowest corner
+        let translate = solids
+            .iter()
+            .flat_map(|(_, solid, _)| solid.surfaces.iter())
+            .flat_map(|surface| surface.rings.iter().flatten())
+            .fold([f64::INFINITY; 3], |lowest, vertex| {
+                [
+                    lowest[0].min(vertex.x),
+                    lowest[1].min(vertex.y),
+                    lowest[2].min(vertex.z),
+                ]
+            });
+        let translate = translate.map(|value| if value.is_finite() { value } else { 0f64 });
+        // vertices closer than a millimetre share a single index once rounded
+        let mut vertices = IndexSet::<[i64; 3]>::new();
+        let mut vertex = |vertex: &Coordinates| {
+            vertices
+                .insert_full([
+                    ((vertex.x - translate[0]) * 1000f64).round() as i64,
+                    ((vertex.y - translate[1]) * 1000f64).round() as i64,
+                    ((vertex.z - translate[2]) * 1000f64).round() as i64,
+                ])
+                .0
+        };
+        let objects = solids
+            .into_iter()
+            .map(|(group, solid, attributes)| {
+                (
+                    group.clone(),
+                    json!({
+                        "type": "Building",
+                        "attributes": attributes,
+                        "geometry": [solid.to_cityjson("2.2", &mut vertex)]
+                    }),
+                )
+            })
+            .collect::<Map<_, _>>();
+        let vertices = vertices.into_iter().collect::<Vec<_>>();
+
+        let mut filename = std::path::Path::new(&self.filename)
+            .file_stem()
+            .unwrap_or_default()
+            .to_os_string();
+
+        filename.push(".city.json");
+
+        let outfilename = std::path::Path::new(directory).join(filename);
+        let filestream = fs::File::create(&outfilename).unwrap();
+        let mut writer = io::BufWriter::new(filestream);
+        let _ = match serde_json::to_writer(
+            &mut writer,
+            &json!({
+                "type": "CityJSON",
+                "version": "1.1",
+                "metadata": {
+                    "referenceSystem": GeoJson::reference_system(&self.metadata)
+                },
+                "transform": {
+                    "scale": [0.001, 0.001, 0.001],
+                    "translate": translate
+                },
+                "CityObjects": objects,
+                "vertices": vertices
+            }),
+        ) {
+            Ok(_) => writer.flush(),
+            _ => panic!("Unable to write file `{outfilename:?}`"),
+        };
+    }
+
+    /// Reference system of the geojson `metadata` as the OGC URL CityJSON expects, the legacy
+    /// `urn:ogc:def:crs:EPSG::<code>` names being converted and any other name kept as is.
+    fn reference_system(metadata: &Value) -> Value {
+        let name = &metadata["crs"]["properties"]["name"];
+
+        match name
+            .as_str()
+            .and_then(|name| name.strip_prefix("urn:ogc:def:crs:EPSG:"))
+            .and_then(|rest| rest.rsplit_once(':'))
+        {
+            Some((_, code)) if !code.is_empty() => {
+                json!(format!("https://www.opengis.net/def/crs/EPSG/0/{code}"))
+            }
+            _ => name.clone(),
+        }
+    }
+
+    /// Height of the ground below the building formed by `paths`, read from the first line
+    /// bounding it that holds the ground property.
+    fn ground(&self, ground: &Ground, paths: &[Path]) -> f64 {
+        match ground {
+            Ground::Fixed(height) => *height,
+            Ground::Attribute(property, default) => paths
+                .iter()
+                .flat_map(|path| path.sides())
+                .filter_map(|(a, b)| {
+                    self.properties
+                        .get(&(a, b))
+                        .or_else(|| self.properties.get(&(b, a)))
+                })
+                .find_map(|properties| properties.get(property).and_then(Value::as_f64))
+                .unwrap_or(*default),
+        }
+    }
+
+    /// Saves the footprint of each group, as the union of its polygons projected onto the ground,
+    /// in a file named after the input file with a `_footprints` suffix and located within
+    /// `directory`.
+    ///
+    /// The area of the roof overhanging the building outline is written where the `lines` of the
+    /// group, as returned by [`GeoJson::parse_groups`], include `Bygningslinje` lines.
+    pub fn save_footprints(
+        &self,
+        groups: &IndexMap<String, Vec<Path>>,
+        lines: &IndexMap<String, Vec<(Coordinates, Coordinates)>>,
+        directory: &str,
+    ) {
+        let property = self.grouping.as_deref().unwrap_or("group");
+        let features = groups
+            .iter()
+            .map(|(group, paths)| {
+                let polygons = paths.iter().map(Polygon::from).collect::<Vec<_>>();
+                let footprint = Footprint::of(&polygons);
+                let overhang = lines
+                    .get(group)
+                    .and_then(|lines| footprint.overhang(lines, &self.linekinds));
+
                 json!({
                     "type": "Feature",
                     "properties": {
-                        "label": identifier.to_string()
+                        property: self
+                            .groups
+                            .get(group)
+                            .cloned()
+                            .unwrap_or_else(|| Value::String(group.clone())),
+                        "area": footprint.area(),
+                        "overhang": overhang
                     },
                     "geometry": {
-                        "type": "Polygon",
-                        "coordinates": [
-                            polygon.path.sequence
-                                .iter()
-                                .map(|coordinates| [ coordinates.x, coordinates.y, coordinates.z ])
-                                .collect::<Vec<_>>()
-                        ]
+                        "type": "MultiPolygon",
+                        "coordinates": footprint
+                            .polygons
+                            .iter()
+                            .map(|polygon| {
+                                polygon
+                                    .iter()
+                                    .map(|ring| {
+                                        ring.iter()
+                                            .map(|coordinates| [coordinates.x, coordinates.y])
+                                            .collect::<Vec<_>>()
+                                    })
+                                    .collect::<Vec<_>>()
+                            })
+                            .collect::<Vec<_>>()
                     }
                 })
             })
             .collect::<Vec<Value>>();
-        // writes to an output file having the same name as the input file but located within `directory`
-        let outfilename = std::path::Path::new(directory).join(&self.filename);
+        let mut filename = std::path::Path::new(&self.filename)
+            .file_stem()
+            .unwrap_or_default()
+            .to_os_string();
+
+        filename.push("_footprints.geojson");
+
+        self.write_as(features, directory, &filename);
+    }
+
+    /// Saves the curved surfaces of each group, as found by
+    /// [`curved_surfaces`](super::curved::curved_surfaces), in a file named after the input file
+    /// with a `_curved` suffix and located within `directory`.
+    ///
+    /// Each surface lists the labels of its member faces, as written by [`GeoJson::save_groups`],
+    /// together with the primitive fitted through them, if any.
+    pub fn save_curved_surfaces(
+        &self,
+        groups: &IndexMap<String, Vec<CurvedSurface>>,
+        directory: &str,
+    ) {
+        let property = self.grouping.as_deref().unwrap_or("group");
+        let features = groups
+            .iter()
+            .flat_map(|(group, surfaces)| surfaces.iter().map(move |surface| (group, surface)))
+            .map(|(group, surface)| {
+                json!({
+                    "type": "Feature",
+                    "properties": {
+                        property: self
+                            .groups
+                            .get(group)
+                            .cloned()
+                            .unwrap_or_else(|| Value::String(group.clone())),
+                        "faces": surface
+                            .faces
+                            .iter()
+                            .map(|path| path.identifier(group))
+                            .collect::<Vec<_>>(),
+                        "primitive": surface.primitive.map(|primitive| primitive.to_json())
+                    },
+                    "geometry": {
+                        "type": "MultiPolygon",
+                        "coordinates": surface
+                            .faces
+                            .iter()
+                            .map(|path| {
+                                [path
+                                    .sequence
+                                    .iter()
+                                    .map(|coordinates| [coordinates.x, coordinates.y, coordinates.z])
+                                    .collect::<Vec<_>>()]
+                            })
+                            .collect::<Vec<_>>()
+                    }
+                })
+            })
+            .collect::<Vec<Value>>();
+        let mut filename = std::path::Path::new(&self.filename)
+            .file_stem()
+            .unwrap_or_default()
+            .to_os_string();
+
+        filename.push("_curved.geojson");
+
+        self.write_as(features, directory, &filename);
+    }
+
+    /// Saves the `diagnostics` as a layer of problem lines, in a file named after the input file
+    /// with a `_diagnostics` suffix and located within `directory`.
+    pub fn save_diagnostics(&self, diagnostics: &Diagnostics, directory: &str) {
+        let mut filename = std::path::Path::new(&self.filename)
+            .file_stem()
+            .unwrap_or_default()
+            .to_os_string();
+
+        filename.push("_diagnostics.geojson");
+
+        self.write_as(diagnostics.features(), directory, &filename);
+    }
+
+    /// Writes `features` to an output file having the same name as the input file but located within `directory`.
+    fn write(&self, features: Vec<Value>, directory: &str) {
+        self.write_as(features, directory, &self.filename);
+    }
+
+    /// Writes `features` to an output file named `filename` located within `directory`.
+    fn write_as(&self, features: Vec<Value>, directory: &str, filename: &std::ffi::OsStr) {
+        let outfilename = std::path::Path::new(directory).join(filename);
         let filestream = fs::File::create(&outfilename).unwrap();
         let mut writer = io::BufWriter::new(filestream);
         let _ = match serde_json::to_writer_pretty(
@@ -149,3 +757,822 @@ impl GeoJson {
         };
     }
 }
+
+/// Layouts of a geojson dataset that can be streamed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layout {
+    /// A single feature collection whose `features` array is walked one element at a time.
+    Collection,
+    /// Newline-delimited features (GeoJSONSeq), each optionally preceded by a record separator.
+    Sequence,
+}
+
+/// Reads a geojson dataset incrementally and processes its lines one building at a time.
+///
+/// Consecutive lines sharing the same value of the grouping property form a building, so the
+/// input is expected to be sorted by that property, as exported from the database, and
+/// [`GeoJsonStream::process`] fails on a building whose lines are not consecutive.
+pub struct GeoJsonStream {
+    /// Location of the input file.
+    location: std::path::PathBuf,
+    /// Name of the file, stored for output file naming.
+    filename: std::ffi::OsString,
+    /// Name of the property identifying the building each line belongs to.
+    property: String,
+    /// Whether the file is a feature collection or a sequence of features.
+    layout: Layout,
+    /// Rules propagating line properties to the output polygons.
+    rules: Vec<PropertyRule>,
+    /// Distances line endpoints are snapped within while extracting the output polygons.
+    snapping: Vec<f64>,
+}
+
+/// Gathers the consecutive lines of a building and writes its polygons once it is complete.
+struct Grouper<'a> {
+    /// Name of the property identifying the building each line belongs to.
+    property: &'a str,
+    /// Identifier of the building being gathered.
+    group: Option<Value>,
+    /// Lines of the building being gathered.
+    lines: Vec<(Coordinates, Coordinates)>,
+    /// Identifiers of the buildings already written, as json text so that `1` and `"1"` differ.
+    closed: HashSet<String>,
+    /// Properties of the lines of the building being gathered.
+    properties: LineProperties,
+    /// Kinds of the lines of the building being gathered.
+    kinds: HashMap<(Coordinates, Coordinates), LineKind>,
+    /// Rules propagating line properties to the output polygons.
+    rules: &'a [PropertyRule],
+    /// Distances line endpoints are snapped within while extracting the output polygons.
+    snapping: &'a [f64],
+    /// Routine extracting the polygons of a building from its lines.
+    polygonalize: Polygonalization<'a>,
+    /// Lines inferred as missing from the building being written.
+    synthetic: HashSet<(Coordinates, Coordinates)>,
+    /// Output stream of polygons.
+    writer: FeatureWriter,
+}
+
+/// Writes features to the output file as soon as they are produced.
+struct FeatureWriter {
+    writer: io::BufWriter<fs::File>,
+    layout: Layout,
+    /// Whether the header of the feature collection was written.
+    started: bool,
+    /// Number of features written so far.
+    count: usize,
+}
+
+/// Walks the top-level members of a feature collection.
+struct CollectionVisitor<'a, 'b> {
+    grouper: &'b mut Grouper<'a>,
+    metadata: &'b mut Map<String, Value>,
+}
+
+/// Walks the `features` array of a feature collection.
+struct FeaturesVisitor<'a, 'b> {
+    grouper: &'b mut Grouper<'a>,
+}
+
+impl GeoJsonStream {
+    /// Prepares the streaming of the geojson dataset `filename`, grouping lines by `property`.
+    ///
+    /// Files with a `.geojsonl`, `.geojsons`, `.geojsonseq`, `.jsonl` or `.ndjson` extension are
+    /// read as newline-delimited features, other files as a single feature collection.
+    pub fn open(filename: &str, property: &str) -> Self {
+        let location = std::path::PathBuf::from(filename);
+        let layout = match location
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("geojsonl" | "geojsons" | "geojsonseq" | "jsonl" | "ndjson") => Layout::Sequence,
+            _ => Layout::Collection,
+        };
+
+        Self {
+            filename: std::ffi::OsString::from(
+                location
+                    .file_name()
+                    .unwrap_or_else(|| panic!("Unable to read file `{}`", filename)),
+            ),
+            location,
+            property: property.to_string(),
+            layout,
+            rules: Vec::new(),
+            snapping: Vec::new(),
+        }
+    }
+
+    /// Sets the `rules` propagating the properties of the input lines to the output polygons they
+    /// bound, lines being related to the sides of the polygons through their endpoints as snapped
+    /// with each of the `tolerances` the polygons are extracted with.
+    pub fn propagate(&mut self, rules: &[PropertyRule], tolerances: &[Tolerances]) {
+        self.rules = rules.to_vec();
+        self.snapping = GeoJson::snapping(tolerances);
+    }
+
+    /// Streams the dataset through `polygonalize` building by building, writing the resulting
+    /// polygons to a file having the same name and layout as the input but located within `directory`.
+    ///
+    /// Fails when either file cannot be read or written, when the input is not valid json, or with
+    /// [`io::ErrorKind::InvalidData`] when the lines of a building are not consecutive, the
+    /// polygons of its first lines having already been written.
+    pub fn process<F>(&self, directory: &str, mut polygonalize: F) -> io::Result<()>
+    where
+        F: FnMut(&Vec<(Coordinates, Coordinates)>) -> Vec<Path>,
+    {
+        self.stream(directory, &mut |lines| (polygonalize(lines), Vec::new()))
+    }
+
+    /// Streams the dataset as [`GeoJsonStream::process`] does, extracting the polygons of each
+    /// building with [`polygonalize_repaired`] and the `tolerances`, polygons bounded by a line
+    /// inferred as missing being marked as synthetic.
+    pub fn process_repaired(&self, directory: &str, tolerances: &[Tolerances]) -> io::Result<()> {
+        self.stream(directory, &mut |lines| {
+            let (paths, diagnostics) = polygonalize_repaired(lines, tolerances);
+
+            (paths, diagnostics.synthetic)
+        })
+    }
+
+    /// Streams the dataset through `polygonalize`, as described in [`GeoJsonStream::process`].
+    fn stream(&self, directory: &str, polygonalize: Polygonalization<'_>) -> io::Result<()> {
+        let reader = io::BufReader::new(fs::File::open(&self.location)?);
+        let outfilename = std::path::Path::new(directory).join(&self.filename);
+        let mut grouper = Grouper {
+            property: &self.property,
+            group: None,
+            lines: Vec::new(),
+            closed: HashSet::new(),
+            properties: IndexMap::new(),
+            kinds: HashMap::new(),
+            rules: &self.rules,
+            snapping: &self.snapping,
+            polygonalize,
+            synthetic: HashSet::new(),
+            writer: FeatureWriter {
+                writer: io::BufWriter::new(fs::File::create(&outfilename)?),
+                layout: self.layout,
+                started: false,
+                count: 0,
+            },
+        };
+        let mut metadata = Map::new();
+
+        match self.layout {
+            Layout::Sequence => {
+                for line in reader.lines() {
+                    let line = line?;
+                    // record separators may precede each feature in GeoJSONSeq
+                    let line = line.trim_start_matches('\u{1e}').trim();
+                    // skips blank lines
+                    if !line.is_empty() {
+                        grouper.push(serde_json::from_str(line)?)?;
+                    }
+                }
+            }
+            Layout::Collection => {
+                serde_json::Deserializer::from_reader(reader).deserialize_map(
+                    CollectionVisitor {
+                        grouper: &mut grouper,
+                        metadata: &mut metadata,
+                    },
+                )?;
+            }
+        }
+        // the last building is complete once the input is exhausted
+        grouper.flush()?;
+        grouper.writer.end(&metadata)
+    }
+}
+
+impl Grouper<'_> {
+    /// Adds a feature, processing the current building first if the feature belongs to another one.
+    fn push(&mut self, element: Value) -> io::Result<()> {
+        if let Some(line) = GeoJson::line(&element) {
+            let group = &element["properties"][self.property];
+
+            if self.group.as_ref() != Some(group) {
+                self.flush()?;
+
+                if self.closed.contains(&group.to_string()) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "lines of building `{group}` are not consecutive, the input must be sorted by `{}`",
+                            self.property
+                        ),
+                    ));
+                }
+
+                self.group = Some(group.clone());
+            }
+
+            if let Some(kind) = GeoJson::kind(&element) {
+                self.kinds.insert(line, kind);
+            }
+
+            if !self.rules.is_empty() {
+                if let Some(properties) = element["properties"].as_object() {
+                    self.properties.insert(line, properties.clone());
+                }
+            }
+
+            self.lines.push(line);
+        }
+
+        Ok(())
+    }
+
+    /// Computes and writes the polygons of the current building.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        let group = self.group.take().unwrap_or(Value::Null);
+
+        self.closed.insert(group.to_string());
+
+        let (paths, synthetic) = (self.polygonalize)(&self.lines);
+
+        self.synthetic
+            .extend(synthetic.iter().map(|(u, v)| Diagnostics::undirected(u, v)));
+        // the roof type is a building-level attribute shared by all its polygons
+        let roof = GeoJson::classified(&paths, &self.kinds);
+        let lines = GeoJson::snapped(&self.properties, self.snapping);
+
+        for path in paths {
+            let mut properties = GeoJson::propagated(self.rules, &lines, &path);
+
+            properties.extend(roof.clone());
+            properties.insert(
+                "label".to_string(),
+                Value::String(path.identifier(&GeoJson::group(&group))),
+            );
+            properties.insert("surface".to_string(), GeoJson::surface(&path));
+
+            if GeoJson::is_synthetic(&self.synthetic, &path) {
+                properties.insert("synthetic".to_string(), Value::Bool(true));
+            }
+            properties.insert(self.property.to_string(), group.clone());
+
+            self.writer
+                .write(&GeoJson::feature(&path, Value::Object(properties)))?;
+        }
+
+        self.lines.clear();
+        self.properties.clear();
+        self.kinds.clear();
+        self.synthetic.clear();
+
+        Ok(())
+    }
+}
+
+impl FeatureWriter {
+    /// Writes the header of a feature collection from the `metadata` read so far.
+    fn begin(&mut self, metadata: &Map<String, Value>) -> io::Result<()> {
+        if self.layout == Layout::Sequence || self.started {
+            return Ok(());
+        }
+
+        self.started = true;
+
+        let metadata = Value::Object(metadata.clone());
+
+        write!(
+            self.writer,
+            "{{\"type\":{},\"name\":{},\"crs\":{},\"features\":[",
+            metadata["type"],
+            metadata["name"],
+            json!({
+                "type": metadata["crs"]["type"],
+                "properties": {
+                    "name": metadata["crs"]["properties"]["name"]
+                }
+            })
+        )
+    }
+
+    fn write(&mut self, feature: &Value) -> io::Result<()> {
+        match self.layout {
+            Layout::Sequence => {
+                serde_json::to_writer(&mut self.writer, feature)?;
+                writeln!(self.writer)?;
+            }
+            Layout::Collection => {
+                if self.count > 0 {
+                    write!(self.writer, ",")?;
+                }
+
+                serde_json::to_writer(&mut self.writer, feature)?;
+            }
+        }
+
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// Terminates the output, writing the header first if the input did not contain any feature.
+    fn end(&mut self, metadata: &Map<String, Value>) -> io::Result<()> {
+        if self.layout == Layout::Collection {
+            self.begin(metadata)?;
+            write!(self.writer, "]}}")?;
+        }
+
+        self.writer.flush()
+    }
+}
+
+impl<'de> Visitor<'de> for CollectionVisitor<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a geojson feature collection")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "features" {
+                // members following the features are not known when the header is written
+                if self.grouper.writer.begin(self.metadata).is_err() {
+                    return Err(serde::de::Error::custom(
+                        "unable to write the output header",
+                    ));
+                }
+
+                map.next_value_seed(FeaturesVisitor {
+                    grouper: self.grouper,
+                })?;
+            } else {
+                self.metadata.insert(key, map.next_value()?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for FeaturesVisitor<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FeaturesVisitor<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of geojson features")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(element) = seq.next_element::<Value>()? {
+            self.grouper
+                .push(element)
+                .map_err(serde::de::Error::custom)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{gable, line};
+
+    #[test]
+    fn stream_sequence_by_building() {
+        let directory = std::env::temp_dir().join("polygonalize-stream");
+        let outdirectory = directory.join("output");
+        let _ = fs::create_dir_all(&outdirectory);
+        // two buildings, the second one being a single triangle
+        let features = [
+            ("a", [[0, 0, 0], [1, 0, 0]]),
+            ("a", [[1, 0, 0], [1, 1, 0]]),
+            ("a", [[1, 1, 0], [0, 1, 0]]),
+            ("a", [[0, 1, 0], [0, 0, 0]]),
+            ("b", [[5, 0, 0], [6, 0, 0]]),
+            ("b", [[6, 0, 0], [5, 1, 0]]),
+            ("b", [[5, 1, 0], [5, 0, 0]]),
+        ];
+        let input = directory.join("buildings.geojsonl");
+        fs::write(
+            &input,
+            features
+                .iter()
+                .map(|(building, coordinates)| {
+                    json!({
+                        "type": "Feature",
+                        "properties": { "bygningsnummer": building },
+                        "geometry": { "type": "LineString", "coordinates": coordinates }
+                    })
+                    .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+        .unwrap();
+        // records the size of each group handed over to the routine
+        let mut sizes = Vec::new();
+
+        GeoJsonStream::open(input.to_str().unwrap(), "bygningsnummer")
+            .process(outdirectory.to_str().unwrap(), |lines| {
+                sizes.push(lines.len());
+                crate::pipeline::polygonalize(lines, &crate::pipeline::TOLERANCES)
+            })
+            .unwrap();
+
+        assert_eq!(vec![4, 3], sizes, "lines must be grouped by building");
+
+        let output = fs::read_to_string(outdirectory.join("buildings.geojsonl")).unwrap();
+        let buildings = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .map(|feature| feature["properties"]["bygningsnummer"].clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![json!("a"), json!("b")], buildings);
+        // the last line of the first building comes after the second building
+        let unsorted = directory.join("unsorted.geojsonl");
+        let mut lines = fs::read_to_string(&input)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let last = lines.remove(3);
+
+        lines.push(last);
+        fs::write(&unsorted, lines.join("\n")).unwrap();
+
+        let error = GeoJsonStream::open(unsorted.to_str().unwrap(), "bygningsnummer")
+            .process(outdirectory.to_str().unwrap(), |lines| {
+                crate::pipeline::polygonalize(lines, &crate::pipeline::TOLERANCES)
+            })
+            .unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+
+    #[test]
+    fn propagate_snapped_lines() {
+        let directory = std::env::temp_dir().join("polygonalize-snapped");
+        let outdirectory = directory.join("output");
+        let _ = fs::create_dir_all(&outdirectory);
+        // a square whose last line, the latest one, was digitised slightly off its first corner
+        let features = [
+            [[0.0, 0.0, 0.0], [10.0, 0.0, 0.0]],
+            [[10.0, 0.0, 0.0], [10.0, 10.0, 0.0]],
+            [[10.0, 10.0, 0.0], [0.0, 10.0, 0.0]],
+            [[0.0, 10.0, 0.0], [0.02, 0.01, 0.0]],
+        ];
+        let input = directory.join("snapped.geojson");
+        fs::write(
+            &input,
+            json!({
+                "type": "FeatureCollection",
+                "name": "snapped",
+                "crs": { "type": "name", "properties": { "name": "urn:ogc:def:crs:EPSG::5972" } },
+                "features": features
+                    .iter()
+                    .enumerate()
+                    .map(|(index, coordinates)| {
+                        json!({
+                            "type": "Feature",
+                            "properties": { "datafangstdato": format!("2020-01-0{}", index + 1) },
+                            "geometry": { "type": "LineString", "coordinates": coordinates }
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let tolerances = [Tolerances {
+            snapping: 0.05,
+            ..Tolerances::default()
+        }];
+        let mut geojson = GeoJson::open(input.to_str().unwrap());
+        let lines = geojson.parse();
+        let paths = crate::pipeline::polygonalize(&lines, &tolerances);
+
+        geojson.propagate(
+            &[PropertyRule::new(
+                "datafangstdato",
+                crate::aggregation::Aggregation::Max,
+            )],
+            &tolerances,
+        );
+        geojson.save(
+            &paths.iter().map(Polygon::from).collect(),
+            outdirectory.to_str().unwrap(),
+        );
+
+        let output = serde_json::from_str::<Value>(
+            &fs::read_to_string(outdirectory.join("snapped.geojson")).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(1, output["features"].as_array().unwrap().len());
+        assert_eq!(
+            json!("2020-01-04"),
+            output["features"][0]["properties"]["datafangstdato"]
+        );
+    }
+
+    #[test]
+    fn groups_keep_the_type_of_values() {
+        assert_eq!("a", GeoJson::group(&json!("a")));
+        assert_eq!("1", GeoJson::group(&json!(1)));
+        assert_eq!("\"1\"", GeoJson::group(&json!("1")));
+        assert_eq!("null", GeoJson::group(&Value::Null));
+        assert_eq!("\"null\"", GeoJson::group(&json!("null")));
+    }
+
+    /// Lines of each building.
+    type Groups = IndexMap<String, Vec<(Coordinates, Coordinates)>>;
+
+    /// Writes two squares sharing a side, each one belonging to its own building, the second one
+    /// lying `gap` metres off the common side, into `touching.geojson` within a temporary
+    /// directory named after `name`. Returns the file opened and grouped by building, with the
+    /// directory to write outputs into.
+    fn touching(name: &str, gap: f64) -> (GeoJson, Groups, std::path::PathBuf) {
+        let directory = std::env::temp_dir().join(format!("polygonalize-{name}"));
+        let outdirectory = directory.join("output");
+        let _ = fs::create_dir_all(&outdirectory);
+        let side = 1.0 + gap;
+        let features = [
+            (1, [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]]),
+            (1, [[1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]),
+            (1, [[1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]),
+            (1, [[0.0, 1.0, 0.0], [0.0, 0.0, 0.0]]),
+            (2, [[side, 0.0, 0.0], [2.0, 0.0, 0.0]]),
+            (2, [[2.0, 0.0, 0.0], [2.0, 1.0, 0.0]]),
+            (2, [[2.0, 1.0, 0.0], [side, 1.0, 0.0]]),
+            (2, [[side, 1.0, 0.0], [side, 0.0, 0.0]]),
+        ];
+        let input = directory.join("touching.geojson");
+        fs::write(
+            &input,
+            json!({
+                "type": "FeatureCollection",
+                "name": "touching",
+                "crs": { "type": "name", "properties": { "name": "urn:ogc:def:crs:EPSG::5972" } },
+                "features": features
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (building, coordinates))| {
+                        json!({
+                            "type": "Feature",
+                            "properties": {
+                                "bygningsnummer": building,
+                                "datafangstdato": format!("2020-01-0{}", index + 1)
+                            },
+                            "geometry": { "type": "LineString", "coordinates": coordinates }
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let mut geojson = GeoJson::open(input.to_str().unwrap());
+        let groups = geojson.parse_groups("bygningsnummer");
+
+        (geojson, groups, outdirectory)
+    }
+
+    /// Features of the geojson file `filename` written within `directory`.
+    fn features(directory: &std::path::Path, filename: &str) -> Vec<Value> {
+        let output =
+            serde_json::from_str::<Value>(&fs::read_to_string(directory.join(filename)).unwrap())
+                .unwrap();
+
+        output["features"].as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn group_touching_buildings() {
+        let (geojson, groups, outdirectory) = touching("groups", 0.0);
+
+        assert_eq!(vec!["1", "2"], groups.keys().collect::<Vec<_>>());
+
+        let polygons = crate::pipeline::polygonalize_groups(&groups, &crate::pipeline::TOLERANCES);
+
+        assert!(
+            polygons.values().all(|paths| paths.len() == 1),
+            "each building must be made of exactly one polygon"
+        );
+
+        geojson.save_groups(&polygons, outdirectory.to_str().unwrap());
+
+        let buildings = features(&outdirectory, "touching.geojson")
+            .iter()
+            .map(|feature| feature["properties"]["bygningsnummer"].clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![json!(1), json!(2)], buildings);
+    }
+
+    #[test]
+    fn propagate_to_grouped_polygons() {
+        let (mut geojson, groups, outdirectory) = touching("propagated", 0.0);
+
+        geojson.propagate(
+            &[PropertyRule::new(
+                "datafangstdato",
+                crate::aggregation::Aggregation::Max,
+            )],
+            &crate::pipeline::TOLERANCES,
+        );
+
+        let polygons = crate::pipeline::polygonalize_groups(&groups, &crate::pipeline::TOLERANCES);
+
+        geojson.save_groups(&polygons, outdirectory.to_str().unwrap());
+
+        let dates = features(&outdirectory, "touching.geojson")
+            .iter()
+            .map(|feature| feature["properties"]["datafangstdato"].clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![json!("2020-01-04"), json!("2020-01-08")], dates);
+    }
+
+    #[test]
+    fn classify_grouped_buildings() {
+        let (geojson, groups, outdirectory) = touching("classified", 0.0);
+        let polygons = crate::pipeline::polygonalize_groups(&groups, &crate::pipeline::TOLERANCES);
+
+        geojson.save_groups(&polygons, outdirectory.to_str().unwrap());
+
+        assert!(features(&outdirectory, "touching.geojson")
+            .iter()
+            .all(|feature| feature["properties"]["roof_type"] == "flat"));
+    }
+
+    #[test]
+    fn save_touching_solids() {
+        // the second square digitised a fraction of a millimetre off the common side
+        let (geojson, groups, outdirectory) = touching("solids", 0.0002);
+        let polygons = crate::pipeline::polygonalize_groups(&groups, &crate::pipeline::TOLERANCES);
+
+        geojson.save_solids(
+            &polygons,
+            &Ground::Attribute("terreng".to_string(), -3f64),
+            outdirectory.to_str().unwrap(),
+        );
+
+        let output = serde_json::from_str::<Value>(
+            &fs::read_to_string(outdirectory.join("touching.city.json")).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(2, output["CityObjects"].as_object().unwrap().len());
+        assert_eq!(
+            json!(-3.0),
+            output["CityObjects"]["1"]["attributes"]["ground"]
+        );
+        assert_eq!(
+            json!("https://www.opengis.net/def/crs/EPSG/0/5972"),
+            output["metadata"]["referenceSystem"]
+        );
+        // both squares share the corners of their common side once rounded to millimetres
+        assert_eq!(12, output["vertices"].as_array().unwrap().len());
+    }
+
+    #[test]
+    fn save_diagnostics_of_groups() {
+        let (geojson, mut groups, outdirectory) = touching("diagnostics", 0.0);
+        // a helping line dangling from the first building
+        let dangling = (
+            Coordinates {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Coordinates {
+                x: -1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        );
+
+        groups["1"].push(dangling);
+
+        let (_, diagnostics) =
+            crate::pipeline::polygonalize_groups_diagnosed(&groups, &crate::pipeline::TOLERANCES);
+
+        geojson.save_diagnostics(&diagnostics, outdirectory.to_str().unwrap());
+
+        let features = features(&outdirectory, "touching_diagnostics.geojson");
+
+        assert_eq!(1, features.len());
+        assert_eq!(json!("pruned"), features[0]["properties"]["issue"]);
+        assert_eq!(
+            json!([[-1.0, 0.0, 0.0], [0.0, 0.0, 0.0]]),
+            features[0]["geometry"]["coordinates"]
+        );
+    }
+
+    #[test]
+    fn mark_synthetic_polygons() {
+        let directory = std::env::temp_dir().join("polygonalize-synthetic");
+        let grouped = directory.join("grouped");
+        let streamed = directory.join("streamed");
+        let _ = fs::create_dir_all(&grouped);
+        let _ = fs::create_dir_all(&streamed);
+        // gable roof whose southern eave was not digitised
+        let input = directory.join("gable.geojson");
+        fs::write(
+            &input,
+            json!({
+                "type": "FeatureCollection",
+                "features": gable()[1..]
+                    .iter()
+                    .map(|(u, v)| json!({
+                        "type": "Feature",
+                        "properties": { "bygningsnummer": 1 },
+                        "geometry": {
+                            "type": "LineString",
+                            "coordinates": [[u.x, u.y, u.z], [v.x, v.y, v.z]]
+                        }
+                    }))
+                    .collect::<Vec<_>>()
+            })
+            .to_string(),
+        )
+        .unwrap();
+        // only the southern face is bounded by the inferred eave
+        let synthetic = |features: Vec<Value>| {
+            features
+                .iter()
+                .map(|feature| feature["properties"]["synthetic"].clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut geojson = GeoJson::open(input.to_str().unwrap());
+        let groups = geojson.parse_groups("bygningsnummer");
+        let (polygons, diagnostics) =
+            crate::pipeline::polygonalize_groups_repaired(&groups, &crate::pipeline::TOLERANCES);
+
+        geojson.synthesize(&diagnostics.synthetic);
+        geojson.save_groups(&polygons, grouped.to_str().unwrap());
+
+        let mut marks = synthetic(features(&grouped, "gable.geojson"));
+        marks.sort_by_key(|mark| mark.is_null());
+
+        assert_eq!(vec![json!(true), Value::Null], marks);
+
+        GeoJsonStream::open(input.to_str().unwrap(), "bygningsnummer")
+            .process_repaired(streamed.to_str().unwrap(), &crate::pipeline::TOLERANCES)
+            .unwrap();
+
+        let mut marks = synthetic(features(&streamed, "gable.geojson"));
+        marks.sort_by_key(|mark| mark.is_null());
+
+        assert_eq!(vec![json!(true), Value::Null], marks);
+    }
+
+    #[test]
+    fn classify_roofs_without_their_walls() {
+        // gable roof closed by both gable walls
+        let lines = [
+            gable(),
+            vec![
+                line([0.0, 0.0, 5.0], [0.0, 8.0, 5.0]),
+                line([10.0, 0.0, 5.0], [10.0, 8.0, 5.0]),
+            ],
+        ]
+        .concat();
+        let paths = crate::pipeline::polygonalize(&lines, &crate::pipeline::TOLERANCES);
+        let properties = GeoJson::classified(&paths, &HashMap::new());
+
+        assert_eq!(2, paths.iter().filter(|path| path.vertical).count());
+        assert_eq!(json!("gable"), properties["roof_type"]);
+    }
+
+    #[test]
+    fn save_touching_footprints() {
+        let (geojson, groups, outdirectory) = touching("footprints", 0.0);
+        let polygons = crate::pipeline::polygonalize_groups(&groups, &crate::pipeline::TOLERANCES);
+
+        geojson.save_footprints(&polygons, &groups, outdirectory.to_str().unwrap());
+
+        let features = features(&outdirectory, "touching_footprints.geojson");
+        let areas = features
+            .iter()
+            .map(|feature| feature["properties"]["area"].clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![json!(1.0), json!(1.0)], areas);
+        // no building outline to compare against
+        assert_eq!(Value::Null, features[0]["properties"]["overhang"]);
+    }
+}
@@ -1,25 +1,69 @@
+pub mod aggregation;
+pub mod classification;
 pub mod coordinates;
+pub mod curved;
+pub mod dcel;
+pub mod diagnostics;
+pub mod evaluation;
+#[cfg(test)]
+mod fixtures;
+pub mod footprint;
+pub mod incremental;
 pub mod io;
 pub mod path;
 pub mod pathgraph;
+pub mod pipeline;
 pub mod plane;
 pub mod polygon;
+#[cfg(feature = "python")]
+mod python;
+pub mod repair;
+pub mod solid;
+pub mod svg;
+pub mod synthetic;
+pub mod tolerances;
+pub mod topology;
+pub mod trace;
+pub mod validation;
 
+pub use aggregation::*;
+pub use classification::*;
 pub use coordinates::*;
+pub use curved::*;
+pub use dcel::*;
+pub use diagnostics::*;
+pub use evaluation::*;
+pub use footprint::*;
+pub use incremental::*;
 pub use io::*;
 pub use path::*;
 pub use pathgraph::*;
+pub use pipeline::*;
 pub use plane::*;
 pub use polygon::*;
+pub use repair::*;
+pub use solid::*;
+pub use svg::*;
+pub use synthetic::*;
+pub use tolerances::*;
+pub use topology::*;
+pub use trace::*;
+pub use validation::*;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixtures::{gable, line};
 
     #[test]
     fn example_two_planes() {
-        // tolerance to compute cross products and determine coplanarity
-        const EPSILON: f64 = 0.1;
+        // tolerances to determine parallelism, coplanarity and valid rings
+        const TOLERANCES: Tolerances = Tolerances {
+            angle: 5.0,
+            distance: 0.1,
+            snapping: 0.0,
+            angle_sum: 5.0,
+        };
         // three dimensional example
         let lines = vec![
             (
@@ -120,7 +164,7 @@ mod tests {
             ),
         ];
         // builds the path graph from oriented lines
-        let graph = pathgraph::PathGraphBuilder::from(&lines, EPSILON).build();
+        let graph = pathgraph::PathGraphBuilder::from(&lines, &TOLERANCES).build();
         // builds polygons as planes from the graph
         let paths = path::PathBuilder::from(&graph).build();
         // this specific inputs contains two planes with positive-oriented normals
@@ -133,8 +177,13 @@ mod tests {
 
     #[test]
     fn example_one_plane_with_dead_ends() {
-        // tolerance to compute cross products and determine coplanarity
-        const EPSILON: f64 = 0.1;
+        // tolerances to determine parallelism, coplanarity and valid rings
+        const TOLERANCES: Tolerances = Tolerances {
+            angle: 5.0,
+            distance: 0.1,
+            snapping: 0.0,
+            angle_sum: 5.0,
+        };
         // same as before but without a connection forming a plane
         let lines = vec![
             (
@@ -223,7 +272,7 @@ mod tests {
             ),
         ];
         // builds the path graph from oriented lines
-        let graph = pathgraph::PathGraphBuilder::from(&lines, EPSILON).build();
+        let graph = pathgraph::PathGraphBuilder::from(&lines, &TOLERANCES).build();
         // builds polygons as planes from the graph
         let paths = path::PathBuilder::from(&graph).build();
         // this specific inputs contains one single plane with positive-oriented normals
@@ -233,4 +282,186 @@ mod tests {
             "this input must be split in exactly one plane"
         );
     }
+
+    #[test]
+    fn example_snapped_endpoints() {
+        // tolerances merging endpoints a few centimetres apart
+        const TOLERANCES: Tolerances = Tolerances {
+            angle: 5.0,
+            distance: 0.1,
+            snapping: 0.05,
+            angle_sum: 5.0,
+        };
+        // a square whose last side was digitised slightly off its first corner
+        let corners = [
+            (0f64, 0f64),
+            (10f64, 0f64),
+            (10f64, 10f64),
+            (0f64, 10f64),
+            (0.02f64, 0.01f64),
+        ];
+        let lines = corners
+            .windows(2)
+            .map(|side| {
+                (
+                    Coordinates {
+                        x: side[0].0,
+                        y: side[0].1,
+                        z: 0f64,
+                    },
+                    Coordinates {
+                        x: side[1].0,
+                        y: side[1].1,
+                        z: 0f64,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        // without snapping the square is left open
+        let graph = pathgraph::PathGraphBuilder::from(
+            &lines,
+            &Tolerances {
+                snapping: 0.0,
+                ..TOLERANCES
+            },
+        )
+        .build();
+        assert_eq!(0, path::PathBuilder::from(&graph).build().len());
+        // snapping closes it
+        let graph = pathgraph::PathGraphBuilder::from(&lines, &TOLERANCES).build();
+        assert_eq!(
+            1,
+            path::PathBuilder::from(&graph).build().len(),
+            "snapped endpoints must close the square"
+        );
+    }
+
+    #[test]
+    fn example_vertical_walls() {
+        // gable roof closed by both gable walls, the western one split by a helping line
+        let lines = [
+            gable(),
+            vec![
+                line([10.0, 0.0, 5.0], [10.0, 8.0, 5.0]),
+                line([0.0, 0.0, 5.0], [0.0, 4.0, 5.0]),
+                line([0.0, 4.0, 5.0], [0.0, 8.0, 5.0]),
+                line([0.0, 4.0, 5.0], [0.0, 4.0, 8.0]),
+            ],
+        ]
+        .concat();
+        let paths = polygonalize(&lines, &TOLERANCES);
+        let walls = paths
+            .iter()
+            .filter(|path| path.vertical)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            5,
+            paths.len(),
+            "the whole western wall must be filtered out"
+        );
+        assert_eq!(3, walls.len());
+        // walls face away from the building
+        for wall in walls {
+            let normal = wall.normal().unwrap();
+
+            assert!(normal.x * (wall.centroid().x - 5.0) > 0.0);
+        }
+    }
+
+    #[test]
+    fn example_mansard_roof() {
+        // steep lower faces rising by 3 m over 1 m and gentle upper ones, along x
+        let profile = [[0.0, 5.0], [1.0, 8.0], [4.0, 9.0], [7.0, 8.0], [8.0, 5.0]];
+        let lines = [
+            profile
+                .iter()
+                .map(|[y, z]| line([0.0, *y, *z], [10.0, *y, *z]))
+                .collect::<Vec<_>>(),
+            profile
+                .windows(2)
+                .flat_map(|side| {
+                    [0.0, 10.0]
+                        .map(|x| line([x, side[0][0], side[0][1]], [x, side[1][0], side[1][1]]))
+                })
+                .collect(),
+        ]
+        .concat();
+        let paths = polygonalize(&lines, &TOLERANCES);
+
+        assert_eq!(4, paths.len());
+        // the steep faces remain roof faces even when found with the loosest tolerances
+        assert!(paths.iter().all(|path| !path.vertical));
+    }
+
+    #[test]
+    fn example_closed_box() {
+        // gable roof closed by its four walls down to the ground
+        let lines = [
+            gable(),
+            [[0.0, 0.0], [10.0, 0.0], [10.0, 8.0], [0.0, 8.0]]
+                .iter()
+                .map(|[x, y]| line([*x, *y, 5.0], [*x, *y, 0.0]))
+                .collect(),
+            vec![
+                line([0.0, 0.0, 0.0], [10.0, 0.0, 0.0]),
+                line([10.0, 0.0, 0.0], [10.0, 8.0, 0.0]),
+                line([10.0, 8.0, 0.0], [0.0, 8.0, 0.0]),
+                line([0.0, 8.0, 0.0], [0.0, 0.0, 0.0]),
+            ],
+        ]
+        .concat();
+
+        for engine in [Engine::DepthFirst, Engine::HalfEdge] {
+            let paths = polygonalize_with(&lines, &TOLERANCES, engine).0;
+
+            // two roof faces, four walls and the ground
+            assert_eq!(7, paths.len(), "{engine:?}");
+            assert_eq!(
+                4,
+                paths.iter().filter(|path| path.vertical).count(),
+                "{engine:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn example_vertical_walls_of_several_buildings() {
+        // two gable roofs closed by their gable walls, side by side along x
+        let building = |offset: f64| {
+            [
+                gable(),
+                vec![
+                    line([0.0, 0.0, 5.0], [0.0, 8.0, 5.0]),
+                    line([10.0, 0.0, 5.0], [10.0, 8.0, 5.0]),
+                ],
+            ]
+            .concat()
+            .into_iter()
+            .map(|(u, v)| {
+                let shift = |c: Coordinates| Coordinates {
+                    x: c.x + offset,
+                    ..c
+                };
+
+                (shift(u), shift(v))
+            })
+            .collect::<Vec<_>>()
+        };
+        let lines = [building(0.0), building(30.0)].concat();
+        let paths = polygonalize(&lines, &TOLERANCES);
+        let walls = paths
+            .iter()
+            .filter(|path| path.vertical)
+            .collect::<Vec<_>>();
+
+        assert_eq!(4, walls.len());
+        // walls face away from their own building rather than from both
+        for wall in walls {
+            let normal = wall.normal().unwrap();
+            let centre = if wall.centroid().x < 20.0 { 5.0 } else { 35.0 };
+
+            assert!(normal.x * (wall.centroid().x - centre) > 0.0);
+        }
+    }
 }
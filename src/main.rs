@@ -0,0 +1,247 @@
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::process::ExitCode;
+
+use polygonalize::{
+    curved_surfaces, evaluate_groups, polygonalize_groups, polygonalize_groups_diagnosed,
+    polygonalize_groups_repaired, Coordinates, CoordinatesVector, GeoJson, JsonLinesTrace,
+    LineKind, Path, PathBuilder, PathGraphBuilder, Projection, SvgRenderer, Tolerances, TraceEvent,
+    TraceSink, TOLERANCES,
+};
+
+const USAGE: &str = "\
+Usage:
+    polygonalize polygonalize <lines.geojson> <output directory> [--group <property>] [--svg <directory>] [--trace <directory>]
+                              [--projection <x,y,z>] [--curved <angle>] [--angle <angle>]
+                              [--distance <distance>] [--diagnostics <directory>] [--repair]
+    polygonalize evaluate <lines.geojson> <reference.geojson> [--group <property>] [--angle <angle>]
+                          [--distance <distance>]
+
+Options:
+    --group <property>    property grouping lines and faces by building [default: bygningsnummer]
+    --angle <angle>       largest angle, in degrees, between lines or plane normals considered
+                          parallel, when searching for rings, matching faces and fitting curved
+                          surfaces
+    --distance <distance> largest distance, in metres, from a point to a plane it lies on, when
+                          searching for rings, matching faces and fitting curved surfaces
+                          [rings are searched with each tolerances of the pipeline, and faces
+                          matched and surfaces fitted with the default ones, unless any of both
+                          is given]
+    --svg <directory>     renders the path graph of each building with each tolerances into
+                          `<building>_<index>.svg` files within the directory
+    --projection <x,y,z>  renders the svg files seen along the given normal, such as that of a
+                          wall, instead of from above
+    --trace <directory>   traces the search for rings of each building with each tolerances into
+                          `<building>_<index>.jsonl` files within the directory
+    --curved <angle>      merges adjacent faces whose normals differ by at most the angle, in
+                          degrees, into curved surfaces saved with a `_curved` suffix
+    --diagnostics <directory>
+                          saves the lines that could not be used as expected, such as dangling
+                          or overused ones, with a `_diagnostics` suffix within the directory
+    --repair              closes the faces left open by missing lines with inferred lines, marking
+                          the faces they bound as synthetic";
+
+/// Arguments of a subcommand, positional ones first.
+struct Arguments {
+    positional: Vec<String>,
+    group: String,
+    svg: Option<String>,
+    trace: Option<String>,
+    projection: Projection,
+    curved: Option<f64>,
+    diagnostics: Option<String>,
+    repair: bool,
+    angle: Option<f64>,
+    distance: Option<f64>,
+}
+
+impl Arguments {
+    fn parse(arguments: impl Iterator<Item = String>) -> Option<Self> {
+        let mut parsed = Self {
+            positional: Vec::new(),
+            group: "bygningsnummer".to_string(),
+            svg: None,
+            trace: None,
+            projection: Projection::TopDown,
+            curved: None,
+            diagnostics: None,
+            repair: false,
+            angle: None,
+            distance: None,
+        };
+        let mut arguments = arguments;
+
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--group" => parsed.group = arguments.next()?,
+                "--svg" => parsed.svg = Some(arguments.next()?),
+                "--trace" => parsed.trace = Some(arguments.next()?),
+                "--projection" => parsed.projection = Arguments::projection(&arguments.next()?)?,
+                "--curved" => parsed.curved = Some(arguments.next()?.parse().ok()?),
+                "--diagnostics" => parsed.diagnostics = Some(arguments.next()?),
+                "--repair" => parsed.repair = true,
+                "--angle" => parsed.angle = Some(arguments.next()?.parse().ok()?),
+                "--distance" => parsed.distance = Some(arguments.next()?.parse().ok()?),
+                option if option.starts_with("--") => return None,
+                _ => parsed.positional.push(argument),
+            }
+        }
+
+        Some(parsed)
+    }
+
+    /// View along the normal given as `x,y,z`.
+    fn projection(normal: &str) -> Option<Projection> {
+        let components = normal
+            .split(',')
+            .map(|component| component.trim().parse::<f64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        let [x, y, z] = components[..] else {
+            return None;
+        };
+
+        CoordinatesVector { x, y, z }
+            .normalize(f64::EPSILON)
+            .map(Projection::Plane)
+    }
+
+    /// Tolerances given by `--angle` and `--distance`, the others defaulting as in
+    /// [`Tolerances::default`].
+    fn tolerances(&self) -> Tolerances {
+        let default = Tolerances::default();
+
+        Tolerances {
+            angle: self.angle.unwrap_or(default.angle),
+            distance: self.distance.unwrap_or(default.distance),
+            ..default
+        }
+    }
+
+    /// Tolerances of each pass of the search, those of the pipeline unless any is given.
+    fn passes(&self) -> Vec<Tolerances> {
+        if self.angle.is_some() || self.distance.is_some() {
+            vec![self.tolerances()]
+        } else {
+            TOLERANCES.to_vec()
+        }
+    }
+}
+
+/// Writes the debugging output requested by the `arguments` for the path graph of each group with
+/// each tolerances.
+fn debug(
+    groups: &IndexMap<String, Vec<(Coordinates, Coordinates)>>,
+    kinds: &HashMap<(Coordinates, Coordinates), LineKind>,
+    arguments: &Arguments,
+) -> io::Result<()> {
+    if arguments.svg.is_none() && arguments.trace.is_none() {
+        return Ok(());
+    }
+
+    for (group, lines) in groups {
+        for (index, tolerances) in arguments.passes().iter().enumerate() {
+            let graph = PathGraphBuilder::from(lines, tolerances).build();
+            let mut events = Vec::<TraceEvent>::new();
+
+            // rings found with these tolerances alone, not those merged over all of them
+            let rings = PathBuilder::from(&graph)
+                .trace(&mut events)
+                .build()
+                .into_iter()
+                .collect::<Vec<Path>>();
+
+            if let Some(directory) = &arguments.trace {
+                let filename =
+                    std::path::Path::new(directory).join(format!("{group}_{index}.jsonl"));
+                let mut trace =
+                    JsonLinesTrace::new(io::BufWriter::new(fs::File::create(filename)?));
+
+                for event in &events {
+                    trace.record(event);
+                }
+            }
+
+            if let Some(directory) = &arguments.svg {
+                let svg = SvgRenderer::from(&graph)
+                    .kinds(kinds)
+                    .rings(&rings)
+                    .events(&events)
+                    .projection(arguments.projection)
+                    .render();
+
+                fs::write(
+                    std::path::Path::new(directory).join(format!("{group}_{index}.svg")),
+                    svg,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut arguments = std::env::args().skip(1);
+    let command = arguments.next();
+
+    match (command.as_deref(), Arguments::parse(arguments)) {
+        (Some("polygonalize"), Some(arguments)) if arguments.positional.len() == 2 => {
+            let mut geojson = GeoJson::open(&arguments.positional[0]);
+            let groups = geojson.parse_groups(&arguments.group);
+            let (polygons, diagnostics) = if arguments.repair {
+                polygonalize_groups_repaired(&groups, &arguments.passes())
+            } else {
+                polygonalize_groups_diagnosed(&groups, &arguments.passes())
+            };
+
+            if let Err(error) = debug(&groups, geojson.kinds(), &arguments) {
+                eprintln!("Unable to write debugging output: {error}");
+
+                return ExitCode::FAILURE;
+            }
+
+            geojson.synthesize(&diagnostics.synthetic);
+            geojson.save_groups(&polygons, &arguments.positional[1]);
+
+            if let Some(directory) = &arguments.diagnostics {
+                geojson.save_diagnostics(&diagnostics, directory);
+            }
+
+            if let Some(angle) = arguments.curved {
+                let surfaces = polygons
+                    .iter()
+                    .map(|(group, paths)| {
+                        (
+                            group.clone(),
+                            curved_surfaces(paths, angle, &arguments.tolerances()),
+                        )
+                    })
+                    .collect();
+
+                geojson.save_curved_surfaces(&surfaces, &arguments.positional[1]);
+            }
+        }
+        (Some("evaluate"), Some(arguments)) if arguments.positional.len() == 2 => {
+            let predicted = polygonalize_groups(
+                &GeoJson::open(&arguments.positional[0]).parse_groups(&arguments.group),
+                &arguments.passes(),
+            );
+            let reference = GeoJson::open(&arguments.positional[1]).parse_faces(&arguments.group);
+            let evaluation = evaluate_groups(&predicted, &reference, &arguments.tolerances());
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&evaluation.summary()).unwrap()
+            );
+        }
+        _ => {
+            eprintln!("{USAGE}");
+
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
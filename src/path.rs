@@ -1,36 +1,58 @@
 use indexmap::IndexMap;
 use indexmap::IndexSet;
+use std::cell::OnceCell;
 use std::collections::BTreeSet;
 use std::hash::Hash;
+use std::hash::Hasher;
 
 use super::coordinates::Coordinates;
 use super::coordinates::CoordinatesVector;
-use super::pathgraph::PathGraph;
+use super::pathgraph::{Edge, PathGraph, VertexId};
 use super::plane::PlaneMatcher;
+use super::tolerances::{Tolerances, VERTICAL};
+use super::trace::{TraceEvent, TraceSink};
 
+/// Closed sequence of coordinates, compared and hashed as a cycle so that the same ring is equal to
+/// itself whatever its starting point or direction, while different rings over the same coordinates
+/// are not.
 #[derive(Clone)]
 pub struct Path {
+    /// Ring starting and ending at its smallest coordinates when built with [`Path::from`], which
+    /// may be reversed in place but not otherwise changed once the path is compared or hashed.
     pub sequence: Vec<Coordinates>,
     pub set: BTreeSet<Coordinates>,
+    /// Whether the ring stands vertically, such as a wall, as tagged by the pipeline with
+    /// [`Path::is_vertical`], and never otherwise.
+    pub vertical: bool,
+    /// Ring in the form given by [`Path::canonical`], computed once when first compared or hashed.
+    cycle: OnceCell<Vec<Coordinates>>,
 }
 
 pub struct PathBuilder<'a> {
     graph: &'a PathGraph,
+    tolerances: Tolerances,
     cache: RecursionCache,
     paths: IndexSet<Path>,
-    stack: Vec<Coordinates>,
-    seen: IndexSet<Coordinates>,
+    stack: Vec<VertexId>,
+    /// Whether each vertex of the graph lies on the stack.
+    seen: Vec<bool>,
+    trace: Option<&'a mut dyn TraceSink>,
 }
 
 struct RecursionCache {
-    table: IndexMap<(Coordinates, Coordinates, Coordinates), Vec<PlaneMatcher>>,
+    table: IndexMap<(VertexId, VertexId, VertexId), Vec<PlaneMatcher>>,
+}
+
+/// Hashes data with 64-bit FNV-1a, whose output does not depend on the platform or the toolchain.
+struct StableHasher {
+    state: u64,
 }
 
 enum RecursionResult {
     Backtrack {
-        destination: Coordinates,
+        destination: VertexId,
         plane: PlaneMatcher,
-        sequence: Vec<Coordinates>,
+        sequence: Vec<VertexId>,
     },
     Closure,
     Done,
@@ -40,42 +62,61 @@ impl<'a> PathBuilder<'a> {
     pub fn from(graph: &'a PathGraph) -> Self {
         Self {
             graph,
+            tolerances: graph.tolerances,
             cache: RecursionCache::new(),
             paths: IndexSet::new(),
             stack: Vec::new(),
-            seen: IndexSet::new(),
+            seen: vec![false; graph.vertices.len()],
+            trace: None,
         }
     }
 
+    /// Overrides the tolerances used to validate rings, which default to those the graph was built with.
+    pub fn tolerances(mut self, tolerances: &Tolerances) -> Self {
+        self.tolerances = *tolerances;
+        self
+    }
+
+    /// Records each decision of the search into `sink`, to find out why a ring was not found.
+    pub fn trace(mut self, sink: &'a mut dyn TraceSink) -> Self {
+        self.trace = Some(sink);
+        self
+    }
+
     pub fn build(mut self) -> IndexSet<Path> {
         for source in self.graph.intersections.keys() {
             self.cache.table.clear();
             self.push(source.0);
-            self.traverse(source, &PlaneMatcher::undefined(self.graph.epsilon));
+            self.traverse(source, &PlaneMatcher::undefined(&self.tolerances));
             self.pop();
         }
 
         self.paths
     }
 
-    fn traverse(
-        &mut self,
-        current: &(Coordinates, Coordinates),
-        plane: &PlaneMatcher,
-    ) -> RecursionResult {
+    fn traverse(&mut self, current: &Edge, plane: &PlaneMatcher) -> RecursionResult {
         if let Some(precedent) = self.precedent() {
             if self
                 .cache
                 .contains(&(precedent.0, precedent.1, current.1), plane)
             {
+                self.record(|graph| TraceEvent::CacheHit {
+                    line: graph.line(&precedent),
+                    successor: graph.coordinates(current.1),
+                });
+
                 return RecursionResult::done();
             }
         }
 
         if current.1 == self.root().unwrap() {
-            self.save(Path::from(&self.stack), plane);
+            self.save(Path::from(&self.graph.ring(&self.stack)), plane);
             RecursionResult::closure()
         } else if self.contains(&current.1) {
+            self.record(|graph| TraceEvent::Backtrack {
+                line: graph.line(current),
+            });
+
             RecursionResult::backtrack(&current.1, plane)
         } else {
             if let Some(matchers) = self.graph.intersections.get(current) {
@@ -92,7 +133,7 @@ impl<'a> PathBuilder<'a> {
                         } = &result
                         {
                             if *destination == current.1 {
-                                self.save(Path::from(sequence), plane);
+                                self.save(Path::from(&self.graph.ring(sequence)), plane);
                             } else {
                                 self.pop();
 
@@ -101,6 +142,11 @@ impl<'a> PathBuilder<'a> {
                         }
 
                         self.pop();
+                    } else {
+                        self.record(|graph| TraceEvent::PlaneMismatch {
+                            line: graph.line(current),
+                            successor: graph.line(successor),
+                        });
                     }
                 }
             }
@@ -115,27 +161,41 @@ impl<'a> PathBuilder<'a> {
     }
 
     fn save(&mut self, path: Path, plane: &PlaneMatcher) {
-        if path.is_valid_on(plane, self.graph.epsilon) {
+        if path.is_valid_on(plane, &self.tolerances) {
+            self.record(|_| TraceEvent::Closure {
+                ring: path.sequence.clone(),
+            });
             self.paths.insert(path.reverse_if_normal_is_negative());
+        } else {
+            self.record(|_| TraceEvent::Rejected {
+                ring: path.sequence.clone(),
+            });
+        }
+    }
+
+    /// Passes the `event` built from the graph to the trace sink, if any.
+    fn record(&mut self, event: impl FnOnce(&PathGraph) -> TraceEvent) {
+        if let Some(sink) = &mut self.trace {
+            sink.record(&event(self.graph));
         }
     }
 
-    fn push(&mut self, coordinates: Coordinates) {
-        self.seen.insert(coordinates);
-        self.stack.push(coordinates);
+    fn push(&mut self, vertex: VertexId) {
+        self.seen[vertex as usize] = true;
+        self.stack.push(vertex);
     }
 
     fn pop(&mut self) {
-        if let Some(coordinates) = self.stack.pop() {
-            self.seen.swap_remove(&coordinates);
+        if let Some(vertex) = self.stack.pop() {
+            self.seen[vertex as usize] = false;
         }
     }
 
-    fn root(&self) -> Option<Coordinates> {
+    fn root(&self) -> Option<VertexId> {
         self.stack.first().copied()
     }
 
-    fn precedent(&self) -> Option<(Coordinates, Coordinates)> {
+    fn precedent(&self) -> Option<Edge> {
         if self.stack.len() < 2 {
             None
         } else {
@@ -146,8 +206,8 @@ impl<'a> PathBuilder<'a> {
         }
     }
 
-    fn contains(&self, coordinates: &Coordinates) -> bool {
-        self.seen.contains(coordinates)
+    fn contains(&self, vertex: &VertexId) -> bool {
+        self.seen[*vertex as usize]
     }
 }
 
@@ -156,18 +216,21 @@ impl Path {
         Self {
             sequence: Vec::new(),
             set: BTreeSet::new(),
+            vertical: false,
+            cycle: OnceCell::new(),
         }
     }
 
-    pub fn from(sequence: &Vec<Coordinates>) -> Self {
+    /// Builds the closed path going through `sequence`, rotated to start at its smallest coordinates.
+    pub fn from(sequence: &[Coordinates]) -> Self {
         let mut result = Self::new();
 
-        for coordinates in sequence {
-            result.push(coordinates);
-        }
+        if let Some(start) = (0..sequence.len()).min_by(|i, j| sequence[*i].cmp(&sequence[*j])) {
+            for coordinates in sequence.iter().cycle().skip(start).take(sequence.len()) {
+                result.push(coordinates);
+            }
 
-        if let Some(root) = sequence.first() {
-            result.sequence.push(*root);
+            result.sequence.push(sequence[start]);
         }
 
         result
@@ -176,12 +239,115 @@ impl Path {
     pub fn push(&mut self, coordinates: &Coordinates) {
         self.sequence.push(*coordinates);
         self.set.insert(*coordinates);
+        self.cycle.take();
     }
 
     pub fn contains(&self, coordinates: &Coordinates) -> bool {
         self.set.contains(coordinates)
     }
 
+    /// Consecutive pairs of coordinates forming the sides of the path.
+    pub fn sides(&self) -> impl Iterator<Item = (Coordinates, Coordinates)> + '_ {
+        self.sequence.windows(2).map(|side| (side[0], side[1]))
+    }
+
+    /// Unit normal of the plane best fitting the ring, following its direction, computed with
+    /// Newell's method which remains valid for concave rings.
+    pub fn normal(&self) -> Option<CoordinatesVector> {
+        self.newell().normalize(f64::EPSILON)
+    }
+
+    /// Area enclosed by the ring, measured in its own plane.
+    pub fn area(&self) -> f64 {
+        self.newell().norm() / 2f64
+    }
+
+    /// Sum of the cross products of the sides of the ring, normal to it and twice its area long.
+    fn newell(&self) -> CoordinatesVector {
+        let mut normal = CoordinatesVector {
+            x: 0f64,
+            y: 0f64,
+            z: 0f64,
+        };
+
+        for (a, b) in self.sides() {
+            normal.x += (a.y - b.y) * (a.z + b.z);
+            normal.y += (a.z - b.z) * (a.x + b.x);
+            normal.z += (a.x - b.x) * (a.y + b.y);
+        }
+
+        normal
+    }
+
+    /// Average of the coordinates of the ring, its closing coordinates excluded.
+    pub fn centroid(&self) -> Coordinates {
+        let count = self.sequence.len().saturating_sub(1).max(1) as f64;
+        let mut centroid = Coordinates {
+            x: 0f64,
+            y: 0f64,
+            z: 0f64,
+        };
+
+        for coordinates in self.sequence.iter().skip(1) {
+            centroid.x += coordinates.x / count;
+            centroid.y += coordinates.y / count;
+            centroid.z += coordinates.z / count;
+        }
+
+        centroid
+    }
+
+    /// Ring of the path, without its closing coordinates, starting at its smallest coordinates and
+    /// walked towards the smaller of their two neighbors, so that it depends neither on where nor on
+    /// which way the ring was traversed.
+    fn canonical(&self) -> &[Coordinates] {
+        self.cycle.get_or_init(|| self.cycle())
+    }
+
+    fn cycle(&self) -> Vec<Coordinates> {
+        let ring = match self.sequence.len() {
+            length if length > 1 && self.sequence.first() == self.sequence.last() => {
+                &self.sequence[..(length - 1)]
+            }
+            _ => &self.sequence[..],
+        };
+        let length = ring.len();
+        let start = (0..length)
+            .min_by(|i, j| ring[*i].cmp(&ring[*j]))
+            .unwrap_or(0);
+        let forward =
+            length < 3 || ring[(start + 1) % length] < ring[(start + length - 1) % length];
+
+        (0..length)
+            .map(|offset| {
+                if forward {
+                    ring[(start + offset) % length]
+                } else {
+                    ring[(start + length - offset) % length]
+                }
+            })
+            .collect()
+    }
+
+    /// Content-derived identifier of the path within `group`, such as a building identifier, which
+    /// stays the same across runs as long as neither the coordinates of the ring nor the group change.
+    pub fn identifier(&self, group: &str) -> String {
+        let mut hasher = StableHasher::new();
+
+        // bytes are fed directly rather than through `Hash`, whose encoding std does not fix, the
+        // group ending with a byte never found in UTF-8
+        hasher.write(group.as_bytes());
+        hasher.write(&[0xff]);
+
+        for coordinates in self.canonical() {
+            hasher.write_u64(coordinates.x.to_bits());
+            hasher.write_u64(coordinates.y.to_bits());
+            hasher.write_u64(coordinates.z.to_bits());
+        }
+
+        format!("{:016x}", hasher.finish())
+    }
+
     fn sum_interior_angles_on(&self, plane: &PlaneMatcher) -> Option<f64> {
         let mut total = 0f64;
 
@@ -206,32 +372,57 @@ impl Path {
             .map(|current| total + current)
     }
 
-    fn is_valid_on(&self, plane: &PlaneMatcher, tolerance: f64) -> bool {
-        if self.sequence.is_empty() || self.sequence.first().ne(&self.sequence.last()) {
+    pub(crate) fn is_valid_on(&self, plane: &PlaneMatcher, tolerances: &Tolerances) -> bool {
+        if self.sequence.is_empty()
+            || self.sequence.first().ne(&self.sequence.last())
+            || !self.lies_on(plane, tolerances.distance)
+        {
             false
         } else if let Some(total) = self.sum_interior_angles_on(plane) {
-            (total - std::f64::consts::PI * (self.sequence.len() - 3) as f64).abs() <= tolerance
+            (total - std::f64::consts::PI * (self.sequence.len() - 3) as f64).abs()
+                <= tolerances.angle_sum.to_radians()
         } else {
             false
         }
     }
 
-    fn reverse_if_normal_is_negative(mut self) -> Self {
-        for index in 0..(self.sequence.len() - 2) {
-            if let Some(normal) =
-                CoordinatesVector::from(&(self.sequence[index], self.sequence[index + 1])).normal(
-                    &CoordinatesVector::from(&(self.sequence[index + 1], self.sequence[index + 2])),
-                    f64::EPSILON,
-                )
-            {
-                if normal.z < 0f64 {
-                    self.sequence.reverse();
-                    break;
-                }
-            }
+    /// Whether all coordinates lie within `distance` of the plane going through the first ones.
+    fn lies_on(&self, plane: &PlaneMatcher, distance: f64) -> bool {
+        match (plane.normal(), self.sequence.first()) {
+            (Some(normal), Some(origin)) => self.sequence.iter().all(|coordinates| {
+                normal
+                    .dot(&CoordinatesVector::unscaled(&(*origin, *coordinates)))
+                    .abs()
+                    <= distance
+            }),
+            _ => false,
+        }
+    }
+
+    /// Path walked so that its normal points upwards, the orientation of vertical paths being
+    /// settled by [`Path::outwards_from`].
+    pub(crate) fn reverse_if_normal_is_negative(mut self) -> Self {
+        // the normal of the whole ring, as the turn at a single corner is reversed at reflex ones
+        if self.normal().is_some_and(|normal| normal.z < 0f64) {
+            self.sequence.reverse();
+        }
+
+        self
+    }
+
+    /// Whether the ring stands vertically, such as a wall or the step between two roof levels,
+    /// within the angle of [`VERTICAL`].
+    pub fn is_vertical(&self) -> bool {
+        self.normal()
+            .is_some_and(|normal| normal.z.abs() <= VERTICAL)
+    }
 
-            if index + 1 >= self.sequence.len() - 2 {
-                break;
+    /// Path walked so that its normal points away from `centre`, such as the centroid of the
+    /// building for a wall.
+    pub fn outwards_from(mut self, centre: &Coordinates) -> Self {
+        if let Some(normal) = self.normal() {
+            if normal.dot(&CoordinatesVector::unscaled(&(*centre, self.centroid()))) < 0f64 {
+                self.sequence.reverse();
             }
         }
 
@@ -239,9 +430,15 @@ impl Path {
     }
 }
 
+impl Default for Path {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PartialEq for Path {
     fn eq(&self, other: &Self) -> bool {
-        self.set.eq(&other.set)
+        self.set.len() == other.set.len() && self.canonical() == other.canonical()
     }
 }
 
@@ -249,7 +446,7 @@ impl Eq for Path {}
 
 impl Hash for Path {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.set
+        self.canonical()
             .iter()
             .for_each(|coordinates| coordinates.hash(state));
     }
@@ -262,11 +459,7 @@ impl RecursionCache {
         }
     }
 
-    fn contains(
-        &self,
-        path: &(Coordinates, Coordinates, Coordinates),
-        plane: &PlaneMatcher,
-    ) -> bool {
+    fn contains(&self, path: &(VertexId, VertexId, VertexId), plane: &PlaneMatcher) -> bool {
         if let Some(matchers) = self.table.get(path) {
             for matcher in matchers {
                 if matcher == plane {
@@ -278,7 +471,7 @@ impl RecursionCache {
         false
     }
 
-    fn insert(&mut self, path: &(Coordinates, Coordinates, Coordinates), plane: &PlaneMatcher) {
+    fn insert(&mut self, path: &(VertexId, VertexId, VertexId), plane: &PlaneMatcher) {
         self.table
             .entry(*path)
             .and_modify(|matchers| {
@@ -288,6 +481,34 @@ impl RecursionCache {
     }
 }
 
+impl StableHasher {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    fn new() -> Self {
+        Self {
+            state: Self::OFFSET,
+        }
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+}
+
 impl RecursionResult {
     fn done() -> Self {
         Self::Done
@@ -297,7 +518,7 @@ impl RecursionResult {
         Self::Closure
     }
 
-    fn backtrack(destination: &Coordinates, plane: &PlaneMatcher) -> Self {
+    fn backtrack(destination: &VertexId, plane: &PlaneMatcher) -> Self {
         Self::Backtrack {
             destination: *destination,
             plane: *plane,
@@ -305,16 +526,128 @@ impl RecursionResult {
         }
     }
 
-    fn enqueue(mut self, coordinates: &Coordinates) -> Self {
+    fn enqueue(mut self, vertex: &VertexId) -> Self {
         if let Self::Backtrack {
             destination: _,
             plane: _,
             sequence,
         } = &mut self
         {
-            sequence.push(*coordinates);
+            sequence.push(*vertex);
         }
 
         self
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn square(offset: f64) -> Vec<Coordinates> {
+        [(0f64, 0f64), (1f64, 0f64), (1f64, 1f64), (0f64, 1f64)]
+            .iter()
+            .map(|(x, y)| Coordinates {
+                x: *x + offset,
+                y: *y,
+                z: 0f64,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn concave_paths_point_upwards() {
+        // L-shaped ring, counterclockwise with a reflex corner
+        let ring = [
+            (0f64, 0f64),
+            (2f64, 0f64),
+            (2f64, 1f64),
+            (1f64, 1f64),
+            (1f64, 2f64),
+            (0f64, 2f64),
+        ]
+        .iter()
+        .map(|(x, y)| Coordinates {
+            x: *x,
+            y: *y,
+            z: 0f64,
+        })
+        .collect::<Vec<_>>();
+        let mut reversed = ring.clone();
+
+        reversed.reverse();
+
+        for path in [Path::from(&ring), Path::from(&reversed)] {
+            let path = path.reverse_if_normal_is_negative();
+
+            assert!(path.normal().unwrap().z > 0f64);
+        }
+    }
+
+    #[test]
+    fn identifier_is_stable() {
+        let path = Path::from(&square(0f64));
+        let mut rotated = square(0f64);
+        rotated.rotate_left(2);
+        let mut reversed = square(0f64);
+        reversed.reverse();
+
+        assert_eq!(
+            path.identifier("1"),
+            Path::from(&rotated).identifier("1"),
+            "the starting point must not matter"
+        );
+        assert_eq!(
+            path.identifier("1"),
+            Path::from(&reversed).identifier("1"),
+            "the direction must not matter"
+        );
+        assert_ne!(path.identifier("1"), path.identifier("2"));
+        assert_ne!(
+            path.identifier("1"),
+            Path::from(&square(1f64)).identifier("1")
+        );
+        assert_eq!(
+            "1d1dfe3680c99559",
+            path.identifier("1"),
+            "identifiers must not change across runs"
+        );
+        assert_eq!("7bcc0000326a8385", path.identifier("building 12"));
+    }
+
+    #[test]
+    fn paths_are_rotated_to_smallest_coordinates() {
+        let mut rotated = square(0f64);
+        rotated.rotate_left(2);
+        let path = Path::from(&rotated);
+
+        assert_eq!(square(0f64)[0], path.sequence[0]);
+        assert_eq!(path.sequence.first(), path.sequence.last());
+        assert_eq!(5, path.sequence.len());
+    }
+
+    #[test]
+    fn paths_compare_as_cycles() {
+        let mut rotated = square(0f64);
+        rotated.rotate_left(1);
+        let mut reversed = square(0f64);
+        reversed.reverse();
+
+        assert!(Path::from(&square(0f64)) == Path::from(&rotated));
+        assert!(Path::from(&square(0f64)) == Path::from(&reversed));
+    }
+
+    #[test]
+    fn rings_over_same_vertices_do_not_collide() {
+        let quad = square(0f64);
+        // same vertices as the quad but crossing itself
+        let bowtie = vec![quad[0], quad[2], quad[1], quad[3]];
+        let mut paths = IndexSet::new();
+
+        paths.insert(Path::from(&quad));
+        paths.insert(Path::from(&bowtie));
+
+        assert!(Path::from(&quad) != Path::from(&bowtie));
+        assert_eq!(2, paths.len(), "both rings must be kept");
+    }
+}
@@ -1,64 +1,96 @@
 use indexmap::IndexMap;
 use indexmap::IndexSet;
+use rstar::primitives::GeomWithData;
+use rstar::{PointDistance, RTree};
+use std::collections::HashMap;
 
 use super::coordinates::Coordinates;
 use super::coordinates::CoordinatesVector;
 use super::plane::PlaneMatcher;
+use super::tolerances::Tolerances;
 
-type ProjectedSuccessors = Vec<(PlaneMatcher, (Coordinates, Coordinates))>;
+/// Index of a vertex within the vertex table of a path graph.
+pub type VertexId = u32;
+/// Line going from a vertex to another one, given by their indices.
+pub type Edge = (VertexId, VertexId);
+
+type ProjectedSuccessors = Vec<(PlaneMatcher, Edge)>;
 
 #[derive(Debug)]
 struct ProjectedIntersection {
-    successor: (Coordinates, Coordinates),
+    successor: Edge,
     angle: Option<f64>,
 }
 
 #[derive(Debug)]
 pub struct PathGraphBuilder {
-    adjacencies: IndexMap<Coordinates, IndexSet<Coordinates>>,
-    epsilon: f64,
+    vertices: IndexSet<Coordinates>,
+    adjacencies: IndexMap<VertexId, IndexSet<VertexId>>,
+    tolerances: Tolerances,
+    pruned: Vec<Vec<Coordinates>>,
 }
 
 #[derive(Debug)]
 pub struct PathGraph {
-    pub intersections: IndexMap<(Coordinates, Coordinates), ProjectedSuccessors>,
-    pub epsilon: f64,
+    /// Coordinates of the vertices, which lines and successors refer to by index.
+    pub vertices: IndexSet<Coordinates>,
+    pub intersections: IndexMap<Edge, ProjectedSuccessors>,
+    pub tolerances: Tolerances,
+    /// Dangling chains of lines removed before building the graph, each one starting at its free end.
+    pub pruned: Vec<Vec<Coordinates>>,
+    /// Intersections where two lines are collinear and hence define no plane, as the sequence of
+    /// the three coordinates involved.
+    pub undefined: Vec<(Coordinates, Coordinates, Coordinates)>,
 }
 
 impl ProjectedIntersection {
     pub fn on_plane(
         plane: &PlaneMatcher,
-        current: (Coordinates, Coordinates),
-        successor: (Coordinates, Coordinates),
+        vertices: &IndexSet<Coordinates>,
+        current: Edge,
+        successor: Edge,
     ) -> Self {
         ProjectedIntersection {
             successor,
             angle: plane.project_angle_between(
-                &CoordinatesVector::from(&current),
-                &CoordinatesVector::from(&successor),
+                &CoordinatesVector::from(&line(vertices, &current)),
+                &CoordinatesVector::from(&line(vertices, &successor)),
             ),
         }
     }
 }
 
+impl PathGraph {
+    /// Coordinates of `vertex`.
+    pub fn coordinates(&self, vertex: VertexId) -> Coordinates {
+        self.vertices[vertex as usize]
+    }
+
+    /// Coordinates of the extremities of `edge`.
+    pub fn line(&self, edge: &Edge) -> (Coordinates, Coordinates) {
+        line(&self.vertices, edge)
+    }
+
+    /// Coordinates of a sequence of `vertices`, such as a ring.
+    pub fn ring(&self, vertices: &[VertexId]) -> Vec<Coordinates> {
+        vertices
+            .iter()
+            .map(|vertex| self.coordinates(*vertex))
+            .collect()
+    }
+}
+
 impl PathGraphBuilder {
-    pub fn from(connections: &Vec<(Coordinates, Coordinates)>, epsilon: f64) -> Self {
-        let mut adjacencies = IndexMap::<Coordinates, IndexSet<Coordinates>>::new();
-
-        for (u, v) in connections {
-            adjacencies
-                .entry(*u)
-                .and_modify(|to| {
-                    to.insert(*v);
-                })
-                .or_insert(IndexSet::from([*v]));
+    pub fn from(connections: &[(Coordinates, Coordinates)], tolerances: &Tolerances) -> Self {
+        let mut vertices = IndexSet::<Coordinates>::new();
+        let mut adjacencies = IndexMap::<VertexId, IndexSet<VertexId>>::new();
 
-            adjacencies
-                .entry(*v)
-                .and_modify(|to| {
-                    to.insert(*u);
-                })
-                .or_insert(IndexSet::from([*u]));
+        for (u, v) in &PathGraphBuilder::snap(connections, tolerances.snapping) {
+            let u = vertices.insert_full(*u).0 as VertexId;
+            let v = vertices.insert_full(*v).0 as VertexId;
+
+            adjacencies.entry(u).or_default().insert(v);
+            adjacencies.entry(v).or_default().insert(u);
         }
 
         let mut leaves = adjacencies
@@ -67,19 +99,41 @@ impl PathGraphBuilder {
             .map(|(leaf, _)| *leaf)
             .collect::<IndexSet<_>>();
 
+        // chains being pruned keyed by the vertex they currently end at
+        let mut chains = IndexMap::<VertexId, Vec<VertexId>>::new();
+        let mut pruned = Vec::<Vec<VertexId>>::new();
+
         while !leaves.is_empty() {
-            let mut updated = IndexSet::<Coordinates>::new();
+            let mut updated = IndexSet::<VertexId>::new();
 
             for leaf in &leaves {
                 if adjacencies.contains_key(leaf) {
-                    if let Some(adjacent) = adjacencies[leaf].iter().next() {
-                        if adjacencies[adjacent].len() <= 2 {
-                            updated.insert(*adjacent);
+                    let mut chain = chains.swap_remove(leaf).unwrap_or_else(|| vec![*leaf]);
+
+                    if let Some(adjacent) = adjacencies[leaf].iter().next().copied() {
+                        chain.push(adjacent);
+
+                        if adjacencies[&adjacent].len() <= 2 {
+                            updated.insert(adjacent);
+                            // two chains meeting at the same vertex form a single isolated one
+                            match chains.swap_remove(&adjacent) {
+                                Some(mut other) => {
+                                    other.extend(chain.iter().rev().skip(1));
+                                    pruned.push(other);
+                                }
+                                None => {
+                                    chains.insert(adjacent, chain);
+                                }
+                            }
+                        } else {
+                            pruned.push(chain);
                         }
 
-                        adjacencies.entry(*adjacent).and_modify(|to| {
+                        adjacencies.entry(adjacent).and_modify(|to| {
                             to.swap_remove(leaf);
                         });
+                    } else if chain.len() > 1 {
+                        pruned.push(chain);
                     }
 
                     adjacencies.swap_remove(leaf);
@@ -89,19 +143,77 @@ impl PathGraphBuilder {
             leaves = updated;
         }
 
+        let pruned = pruned
+            .iter()
+            .map(|chain| {
+                chain
+                    .iter()
+                    .map(|vertex| vertices[*vertex as usize])
+                    .collect()
+            })
+            .collect();
+
         Self {
+            vertices,
             adjacencies,
-            epsilon,
+            tolerances: *tolerances,
+            pruned,
+        }
+    }
+
+    /// Merges line endpoints lying within `distance` of an endpoint met before, dropping the
+    /// lines collapsing to a single point.
+    fn snap(
+        connections: &[(Coordinates, Coordinates)],
+        distance: f64,
+    ) -> Vec<(Coordinates, Coordinates)> {
+        let snapped = PathGraphBuilder::snapped(connections, distance);
+        let snap = |coordinates: &Coordinates| *snapped.get(coordinates).unwrap_or(coordinates);
+
+        connections
+            .iter()
+            .map(|(u, v)| (snap(u), snap(v)))
+            .filter(|(u, v)| u != v)
+            .collect()
+    }
+
+    /// Endpoints of `connections` merged into another endpoint met before and lying within
+    /// `distance` of it, as done when building the graph, mapped to the vertex they are merged
+    /// into, so that input lines can be related to the sides of the rings found.
+    pub fn snapped(
+        connections: &[(Coordinates, Coordinates)],
+        distance: f64,
+    ) -> HashMap<Coordinates, Coordinates> {
+        let mut snapped = HashMap::<Coordinates, Coordinates>::new();
+
+        if distance <= 0f64 {
+            return snapped;
+        }
+
+        let mut vertices = RTree::<GeomWithData<[f64; 3], Coordinates>>::new();
+
+        for coordinates in connections.iter().flat_map(|(u, v)| [u, v]) {
+            let point = [coordinates.x, coordinates.y, coordinates.z];
+
+            match vertices.nearest_neighbor(&point) {
+                Some(nearest) if nearest.distance_2(&point) <= distance * distance => {
+                    if nearest.data != *coordinates {
+                        snapped.insert(*coordinates, nearest.data);
+                    }
+                }
+                _ => vertices.insert(GeomWithData::new(point, *coordinates)),
+            }
         }
+
+        snapped
     }
 
     pub fn build(&self) -> PathGraph {
-        let mut undefined =
-            IndexMap::<(Coordinates, Coordinates), (Coordinates, Coordinates)>::new();
-        let mut intersections = IndexMap::<
-            (Coordinates, Coordinates),
-            Vec<(PlaneMatcher, Vec<ProjectedIntersection>)>,
-        >::new();
+        let vertices = &self.vertices;
+        let mut undefined = IndexMap::<Edge, Edge>::new();
+        let mut junctions = Vec::<(Coordinates, Coordinates, Coordinates)>::new();
+        let mut intersections =
+            IndexMap::<Edge, Vec<(PlaneMatcher, Vec<ProjectedIntersection>)>>::new();
 
         for (intersection, neighbors) in &self.adjacencies {
             for u in neighbors {
@@ -112,12 +224,26 @@ impl PathGraphBuilder {
                 for v in neighbors {
                     if u != v {
                         let adjacent = (*intersection, *v);
-                        let plane = PlaneMatcher::between(&incident, &adjacent, self.epsilon);
+                        let plane = PlaneMatcher::between(
+                            &line(vertices, &incident),
+                            &line(vertices, &adjacent),
+                            &self.tolerances,
+                        );
 
                         intersections.entry(adjacent).or_default();
 
                         if plane.is_undefined() {
                             undefined.insert(incident, adjacent);
+
+                            let (u, w, v) = (
+                                vertices[*u as usize],
+                                vertices[*intersection as usize],
+                                vertices[*v as usize],
+                            );
+
+                            if u < v {
+                                junctions.push((u, w, v));
+                            }
                         } else if let Some(matchers) = intersections.get_mut(&incident) {
                             let mut matching =
                                 Option::<(&PlaneMatcher, &mut Vec<ProjectedIntersection>)>::None;
@@ -125,7 +251,7 @@ impl PathGraphBuilder {
 
                             for (matcher, successors) in matchers {
                                 if let Some(value) = matcher.coplanarity_with(&plane) {
-                                    if value < coplanarity && value <= self.epsilon {
+                                    if value < coplanarity && value <= self.tolerances.sine() {
                                         coplanarity = value;
                                         matching = Some((matcher, successors));
                                     }
@@ -134,14 +260,14 @@ impl PathGraphBuilder {
 
                             if let Some((matcher, successors)) = matching {
                                 successors.push(ProjectedIntersection::on_plane(
-                                    matcher, incident, adjacent,
+                                    matcher, vertices, incident, adjacent,
                                 ));
                             } else {
                                 intersections.entry(incident).and_modify(|matchers| {
                                     matchers.push((
                                         plane,
                                         vec![ProjectedIntersection::on_plane(
-                                            &plane, incident, adjacent,
+                                            &plane, vertices, incident, adjacent,
                                         )],
                                     ));
                                 });
@@ -156,14 +282,14 @@ impl PathGraphBuilder {
             if let Some(matchers) = intersections.get_mut(incident) {
                 for (matcher, successors) in matchers {
                     successors.push(ProjectedIntersection::on_plane(
-                        matcher, *incident, *adjacent,
+                        matcher, vertices, *incident, *adjacent,
                     ));
                 }
             }
 
             intersections.entry(*incident).and_modify(|matchers| {
                 matchers.push((
-                    PlaneMatcher::undefined(self.epsilon),
+                    PlaneMatcher::undefined(&self.tolerances),
                     vec![ProjectedIntersection {
                         successor: *adjacent,
                         angle: None,
@@ -173,6 +299,7 @@ impl PathGraphBuilder {
         }
 
         PathGraph {
+            vertices: self.vertices.clone(),
             intersections: intersections
                 .iter()
                 .map(|(source, matchers)| {
@@ -194,7 +321,78 @@ impl PathGraphBuilder {
                     )
                 })
                 .collect(),
-            epsilon: self.epsilon,
+            tolerances: self.tolerances,
+            pruned: self.pruned.clone(),
+            undefined: junctions,
+        }
+    }
+}
+
+/// Coordinates of the extremities of `edge` within `vertices`.
+fn line(vertices: &IndexSet<Coordinates>, edge: &Edge) -> (Coordinates, Coordinates) {
+    (vertices[edge.0 as usize], vertices[edge.1 as usize])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::path::PathBuilder;
+    use crate::pipeline::{polygonalize, TOLERANCES};
+    use std::time::Instant;
+
+    /// Lines of a tile of `count` by `count` gable roofs laid out side by side, each one sharing
+    /// its gable ends with its neighbours like terraced houses.
+    fn terraced_tile(count: usize) -> Vec<(Coordinates, Coordinates)> {
+        let point = |x: usize, y: f64, z: f64| Coordinates {
+            x: 10f64 * x as f64,
+            y,
+            z,
+        };
+        let mut lines = Vec::new();
+
+        for row in 0..count {
+            let y = 12f64 * row as f64;
+
+            for x in 0..count {
+                lines.push((point(x, y, 5.0), point(x + 1, y, 5.0)));
+                lines.push((point(x, y + 4.0, 8.0), point(x + 1, y + 4.0, 8.0)));
+                lines.push((point(x, y + 8.0, 5.0), point(x + 1, y + 8.0, 5.0)));
+            }
+
+            for x in 0..=count {
+                lines.push((point(x, y, 5.0), point(x, y + 4.0, 8.0)));
+                lines.push((point(x, y + 4.0, 8.0), point(x, y + 8.0, 5.0)));
+            }
+        }
+
+        lines
+    }
+
+    #[test]
+    fn terraced_tile_faces() {
+        // coplanar faces of neighbouring houses also form larger rings, filtered out afterwards
+        assert_eq!(18, polygonalize(&terraced_tile(3), &TOLERANCES[1..2]).len());
+    }
+
+    /// Times building the graph and searching its rings on a dense tile, to be run in release
+    /// mode with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn terraced_tile_timing() {
+        for count in [10, 20, 40] {
+            let lines = terraced_tile(count);
+            let start = Instant::now();
+            let graph = PathGraphBuilder::from(&lines, &TOLERANCES[1]).build();
+            let built = start.elapsed();
+            let paths = PathBuilder::from(&graph).build();
+
+            println!(
+                "{} lines: graph built in {:?}, {} rings found in {:?}",
+                lines.len(),
+                built,
+                paths.len(),
+                start.elapsed() - built
+            );
         }
     }
 }
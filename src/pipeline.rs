@@ -0,0 +1,253 @@
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+
+use super::coordinates::Coordinates;
+use super::dcel::FaceBuilder;
+use super::diagnostics::Diagnostics;
+use super::path::{Path, PathBuilder};
+use super::pathgraph::PathGraphBuilder;
+use super::polygon::Polygon;
+use super::repair::infer_missing_edges;
+use super::tolerances::Tolerances;
+
+/// Tolerances tried one after the other, stricter ones first, when searching for planes.
+pub const TOLERANCES: [Tolerances; 4] = [
+    Tolerances {
+        angle: 0.25,
+        distance: 0.05,
+        snapping: 0.0,
+        angle_sum: 0.25,
+    },
+    Tolerances {
+        angle: 3.0,
+        distance: 0.1,
+        snapping: 0.0,
+        angle_sum: 3.0,
+    },
+    Tolerances {
+        angle: 15.0,
+        distance: 0.25,
+        snapping: 0.0,
+        angle_sum: 15.0,
+    },
+    Tolerances {
+        angle: 30.0,
+        distance: 0.5,
+        snapping: 0.0,
+        angle_sum: 30.0,
+    },
+];
+
+/// Engines searching a path graph for rings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Depth-first search of [`PathBuilder`], following every combination of successors.
+    #[default]
+    DepthFirst,
+    /// Face enumeration of [`FaceBuilder`], walking the half-edges of each plane once.
+    HalfEdge,
+}
+
+/// Extracts the fundamental polygons formed by `lines`, merging the paths found with each of the `tolerances`.
+pub fn polygonalize(lines: &[(Coordinates, Coordinates)], tolerances: &[Tolerances]) -> Vec<Path> {
+    polygonalize_diagnosed(lines, tolerances).0
+}
+
+/// Extracts the fundamental polygons formed by `lines` as [`polygonalize`] does, together with the
+/// diagnostics of the lines that could not be used as expected.
+pub fn polygonalize_diagnosed(
+    lines: &[(Coordinates, Coordinates)],
+    tolerances: &[Tolerances],
+) -> (Vec<Path>, Diagnostics) {
+    polygonalize_with(lines, tolerances, Engine::default())
+}
+
+/// Extracts the fundamental polygons formed by `lines` as [`polygonalize_diagnosed`] does, the
+/// rings of each path graph being searched with the given `engine`.
+pub fn polygonalize_with(
+    lines: &[(Coordinates, Coordinates)],
+    tolerances: &[Tolerances],
+    engine: Engine,
+) -> (Vec<Path>, Diagnostics) {
+    // all paths
+    let mut paths = IndexSet::<Path>::new();
+    // problems met along the way
+    let mut diagnostics = Diagnostics::new();
+    // tries different thresholds
+    for tolerances in tolerances {
+        // computes successors along each computing plane using the adjacency matrix
+        let graph = PathGraphBuilder::from(lines, tolerances).build();
+        // keeps track of pruned lines and undefined planes
+        diagnostics.record(&graph);
+        // constructs all paths from the graph using the same tolerances
+        match engine {
+            Engine::DepthFirst => paths.extend(PathBuilder::from(&graph).build()),
+            Engine::HalfEdge => paths.extend(FaceBuilder::from(&graph).build()),
+        }
+    }
+    // maps to polygons to filter them
+    let unfiltered = paths.iter().map(Polygon::from).collect::<Vec<Polygon>>();
+    // removes redundant polygons, telling walls apart once and for all
+    let paths = Polygon::filter_fundamental_polygons_inefficient(unfiltered)
+        .iter()
+        .map(|polygon| {
+            let mut path = polygon.path.clone();
+
+            path.vertical = path.is_vertical();
+            path
+        })
+        .collect::<Vec<_>>();
+    // walls face away from the building
+    let paths = outwards(paths);
+    // checks how often each line is used
+    diagnostics.cover(lines, &paths, tolerances);
+
+    (paths, diagnostics)
+}
+
+/// Extracts the fundamental polygons formed by `lines` as [`polygonalize_diagnosed`] does, after
+/// closing open chains with the lines inferred as missing, reported as synthetic lines.
+pub fn polygonalize_repaired(
+    lines: &[(Coordinates, Coordinates)],
+    tolerances: &[Tolerances],
+) -> (Vec<Path>, Diagnostics) {
+    let mut repaired = lines.to_vec();
+    let mut synthetic = Vec::<(Coordinates, Coordinates)>::new();
+    // looser tolerances only close the chains left open by stricter ones
+    for tolerances in tolerances {
+        let inferred = infer_missing_edges(&repaired, tolerances);
+
+        repaired.extend(&inferred);
+        synthetic.extend(inferred);
+    }
+
+    let (paths, mut diagnostics) = polygonalize_diagnosed(&repaired, tolerances);
+
+    diagnostics.synthetic = synthetic;
+
+    (paths, diagnostics)
+}
+
+/// Extracts the fundamental polygons of each group of lines separately, such as the lines of each
+/// building, so that lines of touching groups are never searched together.
+pub fn polygonalize_groups(
+    groups: &IndexMap<String, Vec<(Coordinates, Coordinates)>>,
+    tolerances: &[Tolerances],
+) -> IndexMap<String, Vec<Path>> {
+    groups
+        .iter()
+        .map(|(group, lines)| (group.clone(), polygonalize(lines, tolerances)))
+        .collect()
+}
+
+/// Extracts the fundamental polygons of each group of lines as [`polygonalize_groups`] does,
+/// together with the diagnostics of all groups.
+pub fn polygonalize_groups_diagnosed(
+    groups: &IndexMap<String, Vec<(Coordinates, Coordinates)>>,
+    tolerances: &[Tolerances],
+) -> (IndexMap<String, Vec<Path>>, Diagnostics) {
+    let mut diagnostics = Diagnostics::new();
+    let polygons = groups
+        .iter()
+        .map(|(group, lines)| {
+            let (paths, found) = polygonalize_diagnosed(lines, tolerances);
+
+            diagnostics.extend(found);
+            (group.clone(), paths)
+        })
+        .collect();
+
+    (polygons, diagnostics)
+}
+
+/// Extracts the fundamental polygons of each group of lines as [`polygonalize_repaired`] does,
+/// together with the diagnostics of all groups, synthetic lines included.
+pub fn polygonalize_groups_repaired(
+    groups: &IndexMap<String, Vec<(Coordinates, Coordinates)>>,
+    tolerances: &[Tolerances],
+) -> (IndexMap<String, Vec<Path>>, Diagnostics) {
+    let mut diagnostics = Diagnostics::new();
+    let polygons = groups
+        .iter()
+        .map(|(group, lines)| {
+            let (paths, found) = polygonalize_repaired(lines, tolerances);
+
+            diagnostics.extend(found);
+            (group.clone(), paths)
+        })
+        .collect();
+
+    (polygons, diagnostics)
+}
+
+/// Walks the vertical `paths` so that their normal points away from the centroid of the vertices
+/// of the faces connected to them, such as the faces of a single building, the orientation of
+/// walls not following from their normal pointing upwards as that of roof faces.
+fn outwards(mut paths: Vec<Path>) -> Vec<Path> {
+    if !paths.iter().any(|path| path.vertical) {
+        return paths;
+    }
+
+    for component in components(&paths) {
+        let vertices = component
+            .iter()
+            .flat_map(|index| paths[*index].sequence.iter().copied())
+            .collect::<IndexSet<_>>();
+        let count = vertices.len() as f64;
+        let centre = Coordinates {
+            x: vertices.iter().map(|vertex| vertex.x).sum::<f64>() / count,
+            y: vertices.iter().map(|vertex| vertex.y).sum::<f64>() / count,
+            z: vertices.iter().map(|vertex| vertex.z).sum::<f64>() / count,
+        };
+
+        for index in component {
+            if paths[index].vertical {
+                paths[index] = std::mem::take(&mut paths[index]).outwards_from(&centre);
+            }
+        }
+    }
+
+    paths
+}
+
+/// Indices of the `paths` connected to each other through shared vertices, one group per
+/// connected component.
+fn components(paths: &[Path]) -> Vec<Vec<usize>> {
+    let mut incident = IndexMap::<Coordinates, Vec<usize>>::new();
+
+    for (index, path) in paths.iter().enumerate() {
+        for vertex in &path.set {
+            incident.entry(*vertex).or_default().push(index);
+        }
+    }
+
+    let mut visited = vec![false; paths.len()];
+    let mut components = Vec::<Vec<usize>>::new();
+
+    for start in 0..paths.len() {
+        if visited[start] {
+            continue;
+        }
+
+        let mut component = vec![start];
+        let mut stack = vec![start];
+
+        visited[start] = true;
+
+        while let Some(current) = stack.pop() {
+            for vertex in &paths[current].set {
+                for neighbour in &incident[vertex] {
+                    if !visited[*neighbour] {
+                        visited[*neighbour] = true;
+                        component.push(*neighbour);
+                        stack.push(*neighbour);
+                    }
+                }
+            }
+        }
+
+        components.push(component);
+    }
+
+    components
+}
@@ -1,49 +1,69 @@
 use super::coordinates::{Coordinates, CoordinatesVector};
+use super::tolerances::{Tolerances, VERTICAL};
+
+/// Horizontal direction the normals of vertical planes are turned towards, at an azimuth of one
+/// radian so that only walls running at that uncommon angle, rather than the diagonal walls a
+/// rule on the larger of both components would flip on, are sensitive to noise.
+const HORIZONTAL: (f64, f64) = (0.540_302_305_868_139_8, 0.841_470_984_807_896_5);
 
 #[derive(Clone, Copy, Debug)]
 struct Plane {
     normal: CoordinatesVector,
     basis: (CoordinatesVector, CoordinatesVector),
+    /// Point of the plane, namely the intersection it was computed at.
+    origin: Coordinates,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct PlaneMatcher {
     plane: Option<Plane>,
-    epsilon: f64,
+    /// Sine of the largest angle between normals of matching planes.
+    sine: f64,
+    /// Largest distance between parallel planes still matching.
+    distance: f64,
 }
 
 impl PlaneMatcher {
     pub fn between(
         current: &(Coordinates, Coordinates),
         successor: &(Coordinates, Coordinates),
-        epsilon: f64,
+        tolerances: &Tolerances,
     ) -> Self {
-        match CoordinatesVector::normal_direction_to(current, successor, epsilon) {
+        match CoordinatesVector::normal_direction_to(current, successor, tolerances.sine()) {
             Some(normal) => {
-                let normal = if normal.z < 0f64 {
+                // normals of vertical planes are horizontal, and follow a fixed direction instead
+                // so that every corner of a wall agrees
+                let upwards = if normal.z.abs() <= VERTICAL {
+                    normal.x * HORIZONTAL.0 + normal.y * HORIZONTAL.1
+                } else {
+                    normal.z
+                };
+                let normal = if upwards < 0f64 {
                     normal.flip()
                 } else {
                     normal
                 };
-                let u = CoordinatesVector::random();
-                let v = normal.normal(&u, f64::EPSILON).unwrap();
+                let (u, v) = normal.orthonormal_basis();
 
                 PlaneMatcher {
                     plane: Some(Plane {
                         normal,
                         basis: (u, v),
+                        origin: current.1,
                     }),
-                    epsilon,
+                    sine: tolerances.sine(),
+                    distance: tolerances.distance,
                 }
             }
-            None => PlaneMatcher::undefined(epsilon),
+            None => PlaneMatcher::undefined(tolerances),
         }
     }
 
-    pub fn undefined(epsilon: f64) -> Self {
+    pub fn undefined(tolerances: &Tolerances) -> Self {
         PlaneMatcher {
             plane: None,
-            epsilon,
+            sine: tolerances.sine(),
+            distance: tolerances.distance,
         }
     }
 
@@ -53,7 +73,7 @@ impl PlaneMatcher {
 
     pub fn is_same_as(&self, other: &Self) -> bool {
         match (self.plane, other.plane) {
-            (Some(p), Some(q)) => p.normal.is_parallel_to(&q.normal, self.epsilon),
+            (Some(p), Some(q)) => p.is_same_as(&q, self.sine, self.distance),
             _ => false,
         }
     }
@@ -61,7 +81,7 @@ impl PlaneMatcher {
     pub fn match_against(&self, other: &Self, relaxed: bool) -> Option<PlaneMatcher> {
         match (self.plane, other.plane) {
             (Some(p), Some(q)) => {
-                if p.normal.is_parallel_to(&q.normal, self.epsilon) || relaxed {
+                if p.is_same_as(&q, self.sine, self.distance) || relaxed {
                     Some(*self)
                 } else {
                     None
@@ -73,6 +93,22 @@ impl PlaneMatcher {
         }
     }
 
+    /// Unit normal of the plane, pointing upwards, if defined.
+    pub fn normal(&self) -> Option<CoordinatesVector> {
+        self.plane.map(|plane| plane.normal)
+    }
+
+    /// Signed distance from the origin of the coordinate system to the plane along its normal, if defined.
+    pub fn offset(&self) -> Option<f64> {
+        self.plane.map(|plane| {
+            plane.normal.dot(&CoordinatesVector {
+                x: plane.origin.x,
+                y: plane.origin.y,
+                z: plane.origin.z,
+            })
+        })
+    }
+
     pub fn project(&self, vector: &CoordinatesVector) -> Option<CoordinatesVector> {
         match self.plane {
             Some(plane) => CoordinatesVector {
@@ -113,8 +149,232 @@ impl PlaneMatcher {
     }
 }
 
+impl Plane {
+    /// Whether both planes have parallel normals, given the `sine` of the largest angle between them,
+    /// and lie within `distance` of each other, so that parallel but separate planes do not match.
+    fn is_same_as(&self, other: &Self, sine: f64, distance: f64) -> bool {
+        // offsets are compared relative to the origin of this plane to preserve precision with
+        // projected coordinates far from the origin of the coordinate system
+        self.normal.is_parallel_to(&other.normal, sine)
+            && self
+                .normal
+                .dot(&CoordinatesVector::unscaled(&(self.origin, other.origin)))
+                .abs()
+                <= distance
+    }
+}
+
 impl PartialEq for PlaneMatcher {
     fn eq(&self, other: &Self) -> bool {
         self.is_same_as(other)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TOLERANCE: f64 = 1e-12;
+
+    /// Unit normals sweeping the sphere, together with the axes and directions close to the vector
+    /// formerly used to seed the basis of every plane.
+    fn normals() -> Vec<CoordinatesVector> {
+        let mut normals = vec![
+            CoordinatesVector::normalized(1f64, 0f64, 0f64),
+            CoordinatesVector::normalized(0f64, 1f64, 0f64),
+            CoordinatesVector::normalized(0f64, 0f64, 1f64),
+            CoordinatesVector::normalized(-1f64, 0f64, 0f64),
+            CoordinatesVector::normalized(0f64, -1f64, 0f64),
+            CoordinatesVector::normalized(0f64, 0f64, -1f64),
+            CoordinatesVector::normalized(
+                0.6867305397408049,
+                0.7265066107991393,
+                0.02427571322228924,
+            ),
+            CoordinatesVector::normalized(
+                0.6867305397408049,
+                0.7265066107991393,
+                0.02427571322228925,
+            ),
+            CoordinatesVector::normalized(0f64, 1e-9, -1f64),
+        ];
+
+        for i in 0..=12 {
+            for j in 0..24 {
+                let polar = std::f64::consts::PI * i as f64 / 12f64;
+                let azimuth = std::f64::consts::PI * j as f64 / 12f64;
+
+                normals.push(CoordinatesVector::normalized(
+                    polar.sin() * azimuth.cos(),
+                    polar.sin() * azimuth.sin(),
+                    polar.cos(),
+                ));
+            }
+        }
+
+        normals
+    }
+
+    #[test]
+    fn basis_is_orthonormal_in_every_orientation() {
+        for normal in normals() {
+            let (u, v) = normal.orthonormal_basis();
+
+            assert!((u.norm() - 1f64).abs() <= TOLERANCE, "{normal:?}");
+            assert!((v.norm() - 1f64).abs() <= TOLERANCE, "{normal:?}");
+            assert!(u.dot(&v).abs() <= TOLERANCE, "{normal:?}");
+            assert!(u.dot(&normal).abs() <= TOLERANCE, "{normal:?}");
+            assert!(v.dot(&normal).abs() <= TOLERANCE, "{normal:?}");
+            // right-handed frame so that projected angles keep their orientation
+            let w = u.normal(&v, f64::EPSILON).unwrap();
+            assert!((w.dot(&normal) - 1f64).abs() <= TOLERANCE, "{normal:?}");
+        }
+    }
+
+    #[test]
+    fn right_angles_are_projected_on_planes_in_every_orientation() {
+        let origin = Coordinates {
+            x: 0f64,
+            y: 0f64,
+            z: 0f64,
+        };
+
+        for normal in normals() {
+            let (u, v) = normal.orthonormal_basis();
+            let a = Coordinates {
+                x: u.x,
+                y: u.y,
+                z: u.z,
+            };
+            let b = Coordinates {
+                x: u.x + v.x,
+                y: u.y + v.y,
+                z: u.z + v.z,
+            };
+            let matcher = PlaneMatcher::between(&(origin, a), &(a, b), &Tolerances::default());
+
+            assert!(!matcher.is_undefined(), "{normal:?}");
+
+            let angle = matcher
+                .project_angle_between(
+                    &CoordinatesVector::from(&(origin, a)),
+                    &CoordinatesVector::from(&(a, b)),
+                )
+                .unwrap();
+            // the normal is flipped upwards, reversing the orientation of downward planes, while
+            // the orientation of vertical planes is left to the rounding of their normal
+            let expected = if normal.z.abs() <= 1e-9 {
+                vec![
+                    std::f64::consts::FRAC_PI_2,
+                    3f64 * std::f64::consts::FRAC_PI_2,
+                ]
+            } else if normal.z < 0f64 {
+                vec![std::f64::consts::FRAC_PI_2]
+            } else {
+                vec![3f64 * std::f64::consts::FRAC_PI_2]
+            };
+
+            assert!(
+                expected
+                    .iter()
+                    .any(|expected| (angle - expected).abs() <= 1e-9),
+                "{normal:?}: {angle}"
+            );
+        }
+    }
+
+    fn horizontal(z: f64) -> PlaneMatcher {
+        let a = Coordinates {
+            x: 0f64,
+            y: 0f64,
+            z,
+        };
+        let b = Coordinates {
+            x: 1f64,
+            y: 0f64,
+            z,
+        };
+        let c = Coordinates {
+            x: 1f64,
+            y: 1f64,
+            z,
+        };
+
+        PlaneMatcher::between(&(a, b), &(b, c), &Tolerances::default())
+    }
+
+    #[test]
+    fn parallel_planes_apart_do_not_match() {
+        let lower = horizontal(0f64);
+        let step = horizontal(0.5f64);
+
+        assert!(lower != step, "a roof step must separate planes");
+        assert!(lower.match_against(&step, false).is_none());
+        assert!((lower.offset().unwrap() - step.offset().unwrap() + 0.5f64).abs() <= TOLERANCE);
+    }
+
+    #[test]
+    fn parallel_planes_within_distance_match() {
+        let plane = horizontal(0f64);
+        let noisy = horizontal(0.05f64);
+
+        assert!(plane == noisy);
+        assert!(plane.match_against(&noisy, false).is_some());
+    }
+
+    #[test]
+    fn corners_of_diagonal_walls_agree() {
+        // a wall running diagonally, its far corner digitised a millimetre either side
+        let corner = |y: f64| {
+            let a = Coordinates {
+                x: 0f64,
+                y: 0f64,
+                z: 0f64,
+            };
+            let b = Coordinates {
+                x: 10f64,
+                y,
+                z: 0f64,
+            };
+            let c = Coordinates { z: 3f64, ..b };
+
+            PlaneMatcher::between(&(a, b), &(b, c), &Tolerances::default())
+                .normal()
+                .unwrap()
+        };
+
+        assert!(corner(10.001).dot(&corner(9.999)) > 0f64);
+    }
+
+    #[test]
+    fn steep_faces_point_upwards() {
+        // lower face of a mansard roof, rising by 3 m over 1 m, at the loosest tolerances
+        let a = Coordinates {
+            x: 0f64,
+            y: 0f64,
+            z: 0f64,
+        };
+        let b = Coordinates {
+            x: 10f64,
+            y: 0f64,
+            z: 0f64,
+        };
+        let c = Coordinates {
+            x: 10f64,
+            y: 1f64,
+            z: 3f64,
+        };
+        let tolerances = Tolerances {
+            angle: 30f64,
+            ..Tolerances::default()
+        };
+
+        for (current, successor) in [((a, b), (b, c)), ((c, b), (b, a))] {
+            let normal = PlaneMatcher::between(&current, &successor, &tolerances)
+                .normal()
+                .unwrap();
+
+            assert!(normal.z > 0f64, "{normal:?}");
+        }
+    }
+}
@@ -1,12 +1,36 @@
 use core::f64;
-use rstar::{Envelope, RTree, RTreeObject, AABB};
+use rstar::{RTreeObject, AABB};
 
-use super::{coordinates::Coordinates, path::Path};
+use super::{
+    coordinates::{Coordinates, CoordinatesVector},
+    path::Path,
+    tolerances::VERTICAL,
+};
+
+/// Largest distance, in metres, from a point to a side of a polygon for the point to lie on it.
+const BOUNDARY: f64 = 1e-6;
 
 #[derive(PartialEq, Clone)]
 pub struct Polygon<'a> {
     pub path: &'a Path,
     pub boundary: (Coordinates, Coordinates),
+    /// Axis plane the polygon compares others on.
+    axes: Axes,
+    /// Unit normal of the polygon, if its area is not null.
+    normal: Option<CoordinatesVector>,
+    /// Smallest and largest coordinates along each axis, from which the bounds of the polygon on
+    /// any axis plane follow.
+    extent: (Coordinates, Coordinates),
+}
+
+/// Axis plane polygons are projected onto to be compared, that of the two axes other than the
+/// dominant component of their normal, so that walls, which collapse onto a line seen from above,
+/// are compared from the side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axes {
+    XY,
+    XZ,
+    YZ,
 }
 
 impl RTreeObject for Polygon<'_> {
@@ -25,10 +49,44 @@ impl<'a> Polygon<'a> {
         Self {
             path,
             boundary: Polygon::boundary(&path.sequence),
+            axes: Axes::of(path),
+            normal: path.normal(),
+            extent: Polygon::extent(&path.sequence),
         }
     }
 
-    fn boundary(path: &Vec<Coordinates>) -> (Coordinates, Coordinates) {
+    fn extent(path: &[Coordinates]) -> (Coordinates, Coordinates) {
+        path.iter().fold(
+            (
+                Coordinates {
+                    x: f64::INFINITY,
+                    y: f64::INFINITY,
+                    z: f64::INFINITY,
+                },
+                Coordinates {
+                    x: f64::NEG_INFINITY,
+                    y: f64::NEG_INFINITY,
+                    z: f64::NEG_INFINITY,
+                },
+            ),
+            |(min, max), coordinates| {
+                (
+                    Coordinates {
+                        x: min.x.min(coordinates.x),
+                        y: min.y.min(coordinates.y),
+                        z: min.z.min(coordinates.z),
+                    },
+                    Coordinates {
+                        x: max.x.max(coordinates.x),
+                        y: max.y.max(coordinates.y),
+                        z: max.z.max(coordinates.z),
+                    },
+                )
+            },
+        )
+    }
+
+    pub(crate) fn boundary(path: &[Coordinates]) -> (Coordinates, Coordinates) {
         let mut min = Coordinates {
             x: f64::INFINITY,
             y: f64::INFINITY,
@@ -61,27 +119,55 @@ impl<'a> Polygon<'a> {
         (min, max)
     }
 
+    /// Area of the projection of a closed `ring` onto the XY plane, positive when counterclockwise.
+    pub fn signed_area(ring: &[Coordinates]) -> f64 {
+        ring.windows(2)
+            .map(|side| side[0].x * side[1].y - side[1].x * side[0].y)
+            .sum::<f64>()
+            / 2f64
+    }
+
     fn contains_boundary_of(&self, other: &Self) -> bool {
-        self.boundary.0.x <= other.boundary.0.x
-            && self.boundary.1.x >= other.boundary.1.x
-            && self.boundary.0.y <= other.boundary.0.y
-            && self.boundary.1.y >= other.boundary.1.y
+        let (min, max) = (
+            self.axes.project(&self.extent.0),
+            self.axes.project(&self.extent.1),
+        );
+        let (other_min, other_max) = (
+            self.axes.project(&other.extent.0),
+            self.axes.project(&other.extent.1),
+        );
+
+        min.0 <= other_min.0 && max.0 >= other_max.0 && min.1 <= other_min.1 && max.1 >= other_max.1
     }
 
     fn contains_point(&self, point: &Coordinates) -> bool {
+        let axes = |coordinates: &Coordinates| self.axes.project(coordinates);
+
         if self.path.contains(point) {
             return true;
         }
 
+        let point = axes(point);
         let n = self.path.sequence.len() - 1;
         let mut inside = false;
 
         for i in 0..n {
-            let a = self.path.sequence[i];
-            let b = self.path.sequence[(i + 1) % n];
+            let a = axes(&self.path.sequence[i]);
+            let b = axes(&self.path.sequence[(i + 1) % n]);
+            // points on a side, such as the ridge ends of a roof seen from above its eaves, lie
+            // within the polygon
+            let (side, offset) = ((b.0 - a.0, b.1 - a.1), (point.0 - a.0, point.1 - a.1));
+            let length = side.0.hypot(side.1);
+            let along = side.0 * offset.0 + side.1 * offset.1;
 
-            if (a.y > point.y) != (b.y > point.y)
-                && point.x < a.x + ((point.y - a.y) * (b.x - a.x) / (b.y - a.y))
+            if (side.0 * offset.1 - side.1 * offset.0).abs() <= BOUNDARY * length
+                && (0f64..=length * length).contains(&along)
+            {
+                return true;
+            }
+
+            if (a.1 > point.1) != (b.1 > point.1)
+                && point.0 < a.0 + ((point.1 - a.1) * (b.0 - a.0) / (b.1 - a.1))
             {
                 inside = !inside;
             }
@@ -104,8 +190,17 @@ impl<'a> Polygon<'a> {
         false
     }
 
+    /// Whether `other` collapses onto a line on the axis plane of the polygon, standing within
+    /// [`VERTICAL`] of perpendicular to it, such as a wall seen from above or from another wall.
+    fn flattens(&self, other: &Self) -> bool {
+        other
+            .normal
+            .is_some_and(|normal| self.axes.across(&normal).abs() <= VERTICAL)
+    }
+
     fn contains(&self, other: &Self) -> bool {
-        self.contains_boundary_of(other)
+        !self.flattens(other)
+            && self.contains_boundary_of(other)
             && other
                 .path
                 .sequence
@@ -133,13 +228,47 @@ impl<'a> Polygon<'a> {
     }
 }
 
+impl Axes {
+    fn of(path: &Path) -> Self {
+        match path.normal() {
+            Some(normal) if normal.z.abs() < normal.x.abs().max(normal.y.abs()) => {
+                if normal.x.abs() >= normal.y.abs() {
+                    Axes::YZ
+                } else {
+                    Axes::XZ
+                }
+            }
+            _ => Axes::XY,
+        }
+    }
+
+    /// Component of `vector` along the axis perpendicular to the axis plane.
+    fn across(&self, vector: &CoordinatesVector) -> f64 {
+        match self {
+            Axes::XY => vector.z,
+            Axes::XZ => vector.y,
+            Axes::YZ => vector.x,
+        }
+    }
+
+    /// Coordinates of `coordinates` on the axis plane.
+    fn project(&self, coordinates: &Coordinates) -> (f64, f64) {
+        match self {
+            Axes::XY => (coordinates.x, coordinates.y),
+            Axes::XZ => (coordinates.x, coordinates.z),
+            Axes::YZ => (coordinates.y, coordinates.z),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::fixtures::path;
 
     #[test]
     fn polygon_test() {
-        let inner = Path::from(&vec![
+        let inner = Path::from(&[
             Coordinates {
                 x: 0f64,
                 y: 0f64,
@@ -181,7 +310,7 @@ mod test {
             //     z: 0f64,
             // },
         ]);
-        let outer = Path::from(&vec![
+        let outer = Path::from(&[
             Coordinates {
                 x: 0f64,
                 y: 0f64,
@@ -218,4 +347,44 @@ mod test {
 
         // filtered.iter().for_each(|p| println!("{:#?}", p.sequence));
     }
+
+    #[test]
+    fn walls_are_compared_from_the_side() {
+        // gable wall and its lower part, split off by a kinked line within the wall
+        let whole = path(&[[0.0, 0.0, 5.0], [0.0, 8.0, 5.0], [0.0, 4.0, 8.0]]);
+        let lower = path(&[[0.0, 0.0, 5.0], [0.0, 8.0, 5.0], [0.0, 4.0, 6.0]]);
+
+        let filtered = Polygon::filter_fundamental_polygons_inefficient(vec![
+            Polygon::from(&whole),
+            Polygon::from(&lower),
+        ]);
+
+        assert_eq!(1, filtered.len());
+        assert!(filtered[0].path == &lower);
+    }
+
+    #[test]
+    fn ridge_ends_lie_within_the_eaves() {
+        // ring closing a gable roof at the eaves, whose ridge ends lie on its sides seen from above
+        let eaves = path(&[
+            [0.0, 0.0, 5.0],
+            [10.0, 0.0, 5.0],
+            [10.0, 8.0, 5.0],
+            [0.0, 8.0, 5.0],
+        ]);
+        let face = path(&[
+            [0.0, 0.0, 5.0],
+            [10.0, 0.0, 5.0],
+            [10.0, 4.0, 8.0],
+            [0.0, 4.0, 8.0],
+        ]);
+
+        let filtered = Polygon::filter_fundamental_polygons_inefficient(vec![
+            Polygon::from(&eaves),
+            Polygon::from(&face),
+        ]);
+
+        assert_eq!(1, filtered.len());
+        assert!(filtered[0].path == &face);
+    }
 }
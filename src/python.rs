@@ -0,0 +1,158 @@
+use indexmap::IndexMap;
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2, PyReadonlyArray3};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use super::coordinates::Coordinates;
+use super::path::Path;
+use super::pipeline::{polygonalize_groups, TOLERANCES};
+use super::solid::SurfaceKind;
+use super::tolerances::Tolerances;
+use super::topology::Face;
+
+/// Closed ring and attributes of each polygon.
+type Polygons<'py> = Vec<(Bound<'py, PyArray2<f64>>, Bound<'py, PyDict>)>;
+
+/// Python module `polygonalize._polygonalize`, wrapped by the `polygonalize` package.
+#[pymodule]
+#[pyo3(name = "_polygonalize")]
+fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(polygonalize_array, module)?)?;
+
+    Ok(())
+}
+
+/// Extracts the fundamental polygons formed by `lines`, an array of shape (N, 2, 3) holding the
+/// endpoints of each line, optionally grouped by building with one label per line in `groups`.
+///
+/// The tolerances of the pipeline are tried one after the other unless any of `angle`, `distance`,
+/// `snapping` or `angle_sum` is given, in which case those alone are used, the others defaulting
+/// as in [`Tolerances::default`].
+///
+/// Returns a list of pairs, the closed ring of each polygon as an array of shape (M, 3) and its
+/// attributes.
+#[pyfunction]
+#[pyo3(
+    name = "polygonalize",
+    signature = (lines, groups = None, *, angle = None, distance = None, snapping = None, angle_sum = None)
+)]
+fn polygonalize_array<'py>(
+    py: Python<'py>,
+    lines: PyReadonlyArray3<'py, f64>,
+    groups: Option<Vec<String>>,
+    angle: Option<f64>,
+    distance: Option<f64>,
+    snapping: Option<f64>,
+    angle_sum: Option<f64>,
+) -> PyResult<Polygons<'py>> {
+    let lines = lines.as_array();
+
+    if lines.shape()[1..] != [2, 3] {
+        return Err(PyValueError::new_err(format!(
+            "lines must be of shape (N, 2, 3), not {:?}",
+            lines.shape()
+        )));
+    }
+
+    if let Some(groups) = &groups {
+        if groups.len() != lines.shape()[0] {
+            return Err(PyValueError::new_err(format!(
+                "{} groups given for {} lines",
+                groups.len(),
+                lines.shape()[0]
+            )));
+        }
+    }
+
+    let tolerances =
+        if angle.is_some() || distance.is_some() || snapping.is_some() || angle_sum.is_some() {
+            let default = Tolerances::default();
+
+            vec![Tolerances {
+                angle: angle.unwrap_or(default.angle),
+                distance: distance.unwrap_or(default.distance),
+                snapping: snapping.unwrap_or(default.snapping),
+                angle_sum: angle_sum.unwrap_or(default.angle_sum),
+            }]
+        } else {
+            TOLERANCES.to_vec()
+        };
+    // lines of each group, all in a single unnamed group when none are given
+    let mut grouped = IndexMap::<String, Vec<(Coordinates, Coordinates)>>::new();
+
+    for (index, line) in lines.outer_iter().enumerate() {
+        let endpoint = |row: usize| Coordinates {
+            x: line[[row, 0]],
+            y: line[[row, 1]],
+            z: line[[row, 2]],
+        };
+        let group = groups
+            .as_ref()
+            .map_or_else(String::new, |groups| groups[index].clone());
+
+        grouped
+            .entry(group)
+            .or_default()
+            .push((endpoint(0), endpoint(1)));
+    }
+    // the search does not touch any python object
+    let polygons = py.detach(|| polygonalize_groups(&grouped, &tolerances));
+
+    polygons
+        .iter()
+        .flat_map(|(group, paths)| paths.iter().map(move |path| (group, path)))
+        .map(|(group, path)| {
+            Ok((
+                ring(path).into_pyarray(py),
+                attributes(py, path, groups.as_ref().map(|_| group.as_str()))?,
+            ))
+        })
+        .collect()
+}
+
+/// Closed ring of the polygon given by `path`, one row per vertex.
+fn ring(path: &Path) -> Array2<f64> {
+    let mut ring = Array2::<f64>::zeros((path.sequence.len(), 3));
+
+    for (mut row, coordinates) in ring.outer_iter_mut().zip(path.sequence.iter()) {
+        row[0] = coordinates.x;
+        row[1] = coordinates.y;
+        row[2] = coordinates.z;
+    }
+
+    ring
+}
+
+/// Attributes of the polygon given by `path`, named as the properties of the geojson output.
+fn attributes<'py>(
+    py: Python<'py>,
+    path: &Path,
+    group: Option<&str>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let face = Face::from(path);
+    // walls face away from their building rather than upwards
+    let normal = match path.normal() {
+        Some(normal) if path.vertical => normal,
+        _ => face.normal,
+    };
+    let surface = if path.vertical {
+        SurfaceKind::Wall
+    } else {
+        SurfaceKind::Roof
+    };
+    let attributes = PyDict::new(py);
+
+    attributes.set_item("label", path.identifier(group.unwrap_or("")))?;
+    attributes.set_item("surface", surface.name())?;
+    attributes.set_item("area", face.area)?;
+    attributes.set_item("slope", face.slope)?;
+    attributes.set_item("normal", (normal.x, normal.y, normal.z))?;
+
+    if let Some(group) = group {
+        attributes.set_item("group", group)?;
+    }
+
+    Ok(attributes)
+}
@@ -0,0 +1,212 @@
+use indexmap::IndexMap;
+use std::collections::HashSet;
+
+use super::coordinates::{Coordinates, CoordinatesVector};
+use super::diagnostics::Diagnostics;
+use super::pathgraph::PathGraphBuilder;
+use super::tolerances::Tolerances;
+
+/// Free end of an open chain of lines, with the chain walked from that end.
+struct End<'a> {
+    chain: &'a [Coordinates],
+    reversed: bool,
+}
+
+impl End<'_> {
+    fn coordinates(&self) -> Coordinates {
+        if self.reversed {
+            self.chain[self.chain.len() - 1]
+        } else {
+            self.chain[0]
+        }
+    }
+
+    /// Coordinates following the free end along the chain.
+    fn next(&self) -> Coordinates {
+        if self.reversed {
+            self.chain[self.chain.len() - 2]
+        } else {
+            self.chain[1]
+        }
+    }
+}
+
+/// Lines closing the open chains formed by `lines`, as when a single line is missing from an
+/// otherwise closed face, which would lose the whole face to leaf pruning.
+///
+/// Two free ends are closed when both chains they end lie on the plane fitted through them
+/// within the distance of the `tolerances`, and when the closing line crosses none of the
+/// `lines` on the ground. Shorter closing lines are preferred and each free end is closed once.
+pub fn infer_missing_edges(
+    lines: &[(Coordinates, Coordinates)],
+    tolerances: &Tolerances,
+) -> Vec<(Coordinates, Coordinates)> {
+    let graph = PathGraphBuilder::from(lines, tolerances).build();
+    let mut degrees = IndexMap::<Coordinates, HashSet<Coordinates>>::new();
+
+    for (u, v) in lines {
+        degrees.entry(*u).or_default().insert(*v);
+        degrees.entry(*v).or_default().insert(*u);
+    }
+
+    let is_free = |coordinates: &Coordinates| {
+        degrees
+            .get(coordinates)
+            .is_some_and(|adjacent| adjacent.len() == 1)
+    };
+    let ends = graph
+        .pruned
+        .iter()
+        .filter(|chain| chain.len() > 1)
+        .flat_map(|chain| {
+            // isolated chains are free at both ends
+            [false, true]
+                .into_iter()
+                .map(move |reversed| End { chain, reversed })
+        })
+        .filter(|end| is_free(&end.coordinates()))
+        .collect::<Vec<_>>();
+    let existing = lines
+        .iter()
+        .map(|(u, v)| Diagnostics::undirected(u, v))
+        .collect::<HashSet<_>>();
+
+    let mut candidates = Vec::<(f64, usize, usize)>::new();
+
+    for (i, a) in ends.iter().enumerate() {
+        for (j, b) in ends.iter().enumerate().skip(i + 1) {
+            let (u, v) = (a.coordinates(), b.coordinates());
+
+            if u == v || existing.contains(&Diagnostics::undirected(&u, &v)) {
+                continue;
+            }
+
+            if is_coplanar(a, b, tolerances) && !lines.iter().any(|line| crosses(&(u, v), line)) {
+                candidates.push((distance(&u, &v), i, j));
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut closed = HashSet::<Coordinates>::new();
+    let mut inferred = Vec::<(Coordinates, Coordinates)>::new();
+
+    for (_, i, j) in candidates {
+        let (u, v) = (ends[i].coordinates(), ends[j].coordinates());
+
+        if !closed.contains(&u) && !closed.contains(&v) {
+            closed.insert(u);
+            closed.insert(v);
+            inferred.push((u, v));
+        }
+    }
+
+    inferred
+}
+
+/// Whether the chains of both free ends lie on the plane through their first line and the line
+/// closing them.
+fn is_coplanar(a: &End, b: &End, tolerances: &Tolerances) -> bool {
+    let (u, v) = (a.coordinates(), b.coordinates());
+
+    match CoordinatesVector::normal_direction_to(&(a.next(), u), &(u, v), tolerances.sine()) {
+        Some(normal) => a.chain.iter().chain(b.chain).all(|coordinates| {
+            normal
+                .dot(&CoordinatesVector::unscaled(&(u, *coordinates)))
+                .abs()
+                <= tolerances.distance
+        }),
+        None => false,
+    }
+}
+
+/// Whether `line` and `other` cross on the ground, extremities shared by both excepted.
+///
+/// Both lines are projected onto the XY plane and their heights ignored: roofs are seen from
+/// above, so a closing line crossing another line in plan would cut through a face even when
+/// passing above or below that line.
+fn crosses(line: &(Coordinates, Coordinates), other: &(Coordinates, Coordinates)) -> bool {
+    let (a, b) = line;
+    let (c, d) = other;
+
+    if a == c || a == d || b == c || b == d {
+        return false;
+    }
+
+    let orientation = |p: &Coordinates, q: &Coordinates, r: &Coordinates| {
+        let cross = (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x);
+
+        (cross > 0f64) as i32 - (cross < 0f64) as i32
+    };
+
+    orientation(a, b, c) * orientation(a, b, d) <= 0
+        && orientation(c, d, a) * orientation(c, d, b) <= 0
+        && overlaps(a.x, b.x, c.x, d.x)
+        && overlaps(a.y, b.y, c.y, d.y)
+}
+
+fn overlaps(a: f64, b: f64, c: f64, d: f64) -> bool {
+    a.min(b) <= c.max(d) && c.min(d) <= a.max(b)
+}
+
+fn distance(u: &Coordinates, v: &Coordinates) -> f64 {
+    CoordinatesVector::unscaled(&(*u, *v)).norm()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{gable, line};
+    use crate::pipeline::{polygonalize, polygonalize_repaired, TOLERANCES};
+
+    #[test]
+    fn lines_cross_in_plan() {
+        let diagonal = line([0.0, 0.0, 0.0], [10.0, 10.0, 0.0]);
+
+        // above the line, below it and sharing one of its extremities
+        assert!(crosses(
+            &diagonal,
+            &line([0.0, 10.0, 5.0], [10.0, 0.0, 5.0])
+        ));
+        assert!(crosses(
+            &diagonal,
+            &line([0.0, 10.0, -5.0], [10.0, 0.0, -5.0])
+        ));
+        assert!(!crosses(
+            &diagonal,
+            &line([0.0, 0.0, 0.0], [10.0, 0.0, 5.0])
+        ));
+    }
+
+    #[test]
+    fn missing_eave_is_inferred() {
+        // gable roof whose southern eave was not digitised
+        let lines = gable()[1..].to_vec();
+
+        assert_eq!(1, polygonalize(&lines, &TOLERANCES).len());
+
+        let (paths, diagnostics) = polygonalize_repaired(&lines, &TOLERANCES);
+
+        assert_eq!(2, paths.len());
+        assert_eq!(1, diagnostics.synthetic.len());
+        assert_eq!(
+            Diagnostics::undirected(&lines[0].0, &lines[1].0),
+            Diagnostics::undirected(&diagnostics.synthetic[0].0, &diagnostics.synthetic[0].1)
+        );
+    }
+
+    #[test]
+    fn crossing_edges_are_not_inferred() {
+        // open square whose closing line would cross a line standing on its own
+        let lines = vec![
+            line([0.0, 0.0, 0.0], [10.0, 0.0, 0.0]),
+            line([10.0, 0.0, 0.0], [10.0, 10.0, 0.0]),
+            line([0.0, 0.0, 0.0], [0.0, 10.0, 0.0]),
+            line([5.0, 8.0, 3.0], [5.0, 12.0, 3.0]),
+        ];
+
+        assert_eq!(1, infer_missing_edges(&lines[..3], &TOLERANCES[0]).len());
+        assert!(infer_missing_edges(&lines, &TOLERANCES[0]).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use indexmap::IndexMap;
+use serde_json::json;
+use serde_json::Value;
+
+use super::coordinates::Coordinates;
+use super::diagnostics::Diagnostics;
+use super::polygon::Polygon;
+use super::topology::RoofTopology;
+
+/// Semantic kinds of the surfaces bounding a building.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SurfaceKind {
+    Roof,
+    Wall,
+    Floor,
+}
+
+/// Planar surface of a solid, made of closed rings oriented with the normal pointing outwards,
+/// the first ring being its exterior and the other ones its holes.
+#[derive(Debug, Clone)]
+pub struct Surface {
+    pub kind: SurfaceKind,
+    pub rings: Vec<Vec<Coordinates>>,
+}
+
+/// Height of the ground buildings stand on, where their walls end.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ground {
+    /// Same height for all buildings.
+    Fixed(f64),
+    /// Height read from a numeric property of the lines of each building, with the height used
+    /// when a building lacks it.
+    Attribute(String, f64),
+}
+
+/// Closed building volume bounded by its roof, walls and floor.
+#[derive(Debug, Clone)]
+pub struct Solid {
+    pub surfaces: Vec<Surface>,
+}
+
+impl SurfaceKind {
+    /// Name of the semantic surface in CityGML and CityJSON.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SurfaceKind::Roof => "RoofSurface",
+            SurfaceKind::Wall => "WallSurface",
+            SurfaceKind::Floor => "GroundSurface",
+        }
+    }
+}
+
+impl Solid {
+    /// Closes the roof formed by `polygons` into a solid, extruding vertical walls from the lines
+    /// bounding the roof down to the `ground` height and adding a floor.
+    ///
+    /// Yields nothing when the roof has no boundary or the ground is not below all of it.
+    pub fn extrude(polygons: &[Polygon<'_>], ground: f64) -> Option<Self> {
+        let topology = RoofTopology::from(polygons, &Default::default());
+
+        if topology.boundaries.is_empty()
+            || topology
+                .boundaries
+                .iter()
+                .flatten()
+                .any(|coordinates| coordinates.z <= ground)
+        {
+            return None;
+        }
+
+        let mut surfaces = topology
+            .faces
+            .iter()
+            .map(|face| {
+                // walls between roof levels are already walked outwards
+                if face.path.vertical {
+                    Surface {
+                        kind: SurfaceKind::Wall,
+                        rings: vec![face.path.sequence.clone()],
+                    }
+                } else {
+                    Surface {
+                        kind: SurfaceKind::Roof,
+                        rings: vec![Solid::upwards(&face.path.sequence)],
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        // the outer boundary is counterclockwise and holes are clockwise, hence the outside of
+        // the building always lies on the right of the boundary
+        for boundary in &topology.boundaries {
+            for side in boundary.windows(2) {
+                let (a, b) = (side[0], side[1]);
+                let (c, d) = (Solid::lowered(&b, ground), Solid::lowered(&a, ground));
+
+                surfaces.push(Surface {
+                    kind: SurfaceKind::Wall,
+                    rings: vec![vec![a, d, c, b, a]],
+                });
+            }
+        }
+        // the floor faces downwards
+        surfaces.push(Surface {
+            kind: SurfaceKind::Floor,
+            rings: topology
+                .boundaries
+                .iter()
+                .map(|boundary| {
+                    boundary
+                        .iter()
+                        .rev()
+                        .map(|coordinates| Solid::lowered(coordinates, ground))
+                        .collect()
+                })
+                .collect(),
+        });
+
+        Some(Self { surfaces })
+    }
+
+    /// Whether the solid is watertight, every side of every ring being shared by exactly two
+    /// rings walking it in opposite directions.
+    pub fn is_manifold(&self) -> bool {
+        let mut sides = IndexMap::<(Coordinates, Coordinates), (usize, usize)>::new();
+
+        for ring in self.surfaces.iter().flat_map(|surface| &surface.rings) {
+            for side in ring.windows(2) {
+                let line = Diagnostics::undirected(&side[0], &side[1]);
+                let entry = sides.entry(line).or_default();
+
+                if line == (side[0], side[1]) {
+                    entry.0 += 1;
+                } else {
+                    entry.1 += 1;
+                }
+            }
+        }
+
+        !sides.is_empty() && sides.values().all(|count| *count == (1, 1))
+    }
+
+    /// CityJSON geometry of the solid at the given level of detail, its rings given as the indices
+    /// returned by `vertex` into the vertices shared by all geometries of a file.
+    pub fn to_cityjson(&self, lod: &str, vertex: &mut impl FnMut(&Coordinates) -> usize) -> Value {
+        let boundaries = self
+            .surfaces
+            .iter()
+            .map(|surface| {
+                surface
+                    .rings
+                    .iter()
+                    // rings are implicitly closed in CityJSON
+                    .map(|ring| {
+                        ring[..ring.len().saturating_sub(1)]
+                            .iter()
+                            .map(&mut *vertex)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let kinds = [SurfaceKind::Roof, SurfaceKind::Wall, SurfaceKind::Floor];
+
+        json!({
+            "type": "Solid",
+            "lod": lod,
+            "boundaries": [boundaries],
+            "semantics": {
+                "surfaces": kinds
+                    .iter()
+                    .map(|kind| json!({ "type": kind.name() }))
+                    .collect::<Vec<_>>(),
+                "values": [
+                    self.surfaces
+                        .iter()
+                        .map(|surface| kinds.iter().position(|kind| *kind == surface.kind))
+                        .collect::<Vec<_>>()
+                ]
+            }
+        })
+    }
+
+    fn lowered(coordinates: &Coordinates, ground: f64) -> Coordinates {
+        Coordinates {
+            z: ground,
+            ..*coordinates
+        }
+    }
+
+    /// Ring walked so that its normal points upwards.
+    fn upwards(ring: &[Coordinates]) -> Vec<Coordinates> {
+        if Polygon::signed_area(ring) < 0f64 {
+            ring.iter().rev().copied().collect()
+        } else {
+            ring.to_vec()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::path;
+
+    #[test]
+    fn gable_roof_is_closed() {
+        let paths = [
+            path(&[
+                [0.0, 0.0, 5.0],
+                [10.0, 0.0, 5.0],
+                [10.0, 4.0, 8.0],
+                [0.0, 4.0, 8.0],
+            ]),
+            path(&[
+                [0.0, 4.0, 8.0],
+                [10.0, 4.0, 8.0],
+                [10.0, 8.0, 5.0],
+                [0.0, 8.0, 5.0],
+            ]),
+        ];
+        let polygons = paths.iter().map(Polygon::from).collect::<Vec<_>>();
+        let solid = Solid::extrude(&polygons, 0f64).unwrap();
+        let count = |kind: SurfaceKind| {
+            solid
+                .surfaces
+                .iter()
+                .filter(|surface| surface.kind == kind)
+                .count()
+        };
+
+        assert_eq!(2, count(SurfaceKind::Roof));
+        // the gable ends are split at the ridge
+        assert_eq!(6, count(SurfaceKind::Wall));
+        assert_eq!(1, count(SurfaceKind::Floor));
+        assert!(solid.is_manifold());
+        // removing a wall opens the solid
+        let mut open = solid.clone();
+        open.surfaces.remove(2);
+        assert!(!open.is_manifold());
+        // the ground must lie below the roof
+        assert!(Solid::extrude(&polygons, 6f64).is_none());
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::coordinates::{Coordinates, CoordinatesVector};
+use super::io::LineKind;
+use super::path::Path;
+use super::pathgraph::PathGraph;
+use super::plane::PlaneMatcher;
+use super::trace::TraceEvent;
+
+/// Colours told apart easily, given in turn to the planes of successors.
+const PALETTE: [&str; 8] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#46f0f0", "#f032e6", "#9a6324",
+];
+
+/// Share of the length of each line drawn when showing the successor chosen after it.
+const SUCCESSOR_LENGTH: f64 = 0.25;
+
+/// Stylesheet of the rendered elements, lines being classed by kind.
+const STYLE: &str = "\
+line { stroke-width: 1.5px; vector-effect: non-scaling-stroke; stroke: #888888; }
+.ridge { stroke: #d62728; }
+.edge { stroke: #1f77b4; }
+.roof-gap { stroke: #ff7f0e; }
+.roof-gap-line { stroke: #9467bd; }
+.building { stroke: #000000; }
+.helping { stroke: #2ca02c; stroke-dasharray: 4 2; }
+.pruned { stroke: #bbbbbb; stroke-dasharray: 2 2; }
+.successor { fill: none; stroke-width: 1px; vector-effect: non-scaling-stroke; }
+.undefined { stroke: #888888; stroke-dasharray: 1 1; }
+.ring { fill: #ffd70040; stroke: #b8860b; stroke-width: 1px; vector-effect: non-scaling-stroke; }
+.rejected { fill: none; stroke: #ff0000; stroke-width: 1px; stroke-dasharray: 3 3; vector-effect: non-scaling-stroke; }
+.mismatch { fill: none; stroke: #ff0000; stroke-width: 1px; stroke-dasharray: 1 2; vector-effect: non-scaling-stroke; }";
+
+/// View the coordinates are drawn from.
+#[derive(Debug, Clone, Copy)]
+pub enum Projection {
+    /// Seen from above, the plane coordinates being kept as they are.
+    TopDown,
+    /// Seen along the given normal, onto the plane orthogonal to it, such as that of a wall.
+    Plane(CoordinatesVector),
+}
+
+/// Renders a path graph to SVG, to find out why a face was missed.
+///
+/// The lines of the graph are coloured by their kind, the chains pruned as leaves are dashed, the
+/// successor chosen after each line on each plane is drawn as a short bend coloured by plane near
+/// the vertex it is chosen at, and the rings found are filled. The rings rejected and the
+/// successors left aside for their plane are drawn in red when the events of a trace are given.
+pub struct SvgRenderer<'a> {
+    graph: &'a PathGraph,
+    kinds: Option<&'a HashMap<(Coordinates, Coordinates), LineKind>>,
+    rings: &'a [Path],
+    events: &'a [TraceEvent],
+    projection: Projection,
+}
+
+impl Projection {
+    /// Coordinates of `coordinates` in the view, the second one pointing down as in SVG.
+    fn apply(&self, coordinates: &Coordinates) -> (f64, f64) {
+        match self {
+            Projection::TopDown => (coordinates.x, -coordinates.y),
+            Projection::Plane(normal) => {
+                let (u, v) = normal.orthonormal_basis();
+                let vector = CoordinatesVector {
+                    x: coordinates.x,
+                    y: coordinates.y,
+                    z: coordinates.z,
+                };
+
+                (vector.dot(&u), -vector.dot(&v))
+            }
+        }
+    }
+}
+
+impl<'a> SvgRenderer<'a> {
+    pub fn from(graph: &'a PathGraph) -> Self {
+        Self {
+            graph,
+            kinds: None,
+            rings: &[],
+            events: &[],
+            projection: Projection::TopDown,
+        }
+    }
+
+    /// Kinds of the lines as digitised, such as those of [`GeoJson::kinds`](super::io::GeoJson::kinds).
+    pub fn kinds(mut self, kinds: &'a HashMap<(Coordinates, Coordinates), LineKind>) -> Self {
+        self.kinds = Some(kinds);
+        self
+    }
+
+    /// Rings found from the graph, filled on top of the lines.
+    pub fn rings(mut self, rings: &'a [Path]) -> Self {
+        self.rings = rings;
+        self
+    }
+
+    /// Events of a trace of the search for rings, such as those replayed with
+    /// [`TraceEvent::replay`].
+    pub fn events(mut self, events: &'a [TraceEvent]) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Overrides the view, which defaults to [`Projection::TopDown`].
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn render(&self) -> String {
+        let mut body = String::new();
+        let mut bounds = Bounds::default();
+        // rings go below so that lines and successors remain visible
+        for ring in self.rings {
+            let points = ring
+                .sequence
+                .iter()
+                .map(|coordinates| self.point(coordinates, &mut bounds))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let _ = writeln!(
+                body,
+                r#"<polygon class="ring" points="{points}"><title>{}</title></polygon>"#,
+                ring.identifier("")
+            );
+        }
+        for event in self.events {
+            match event {
+                TraceEvent::Rejected { ring } => {
+                    let points = ring
+                        .iter()
+                        .map(|coordinates| self.point(coordinates, &mut bounds))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+
+                    let _ = writeln!(body, r#"<polygon class="rejected" points="{points}"/>"#);
+                }
+                TraceEvent::PlaneMismatch { line, successor } => {
+                    let points = self.bend(&line.0, &line.1, &successor.1, &mut bounds);
+
+                    let _ = writeln!(body, r#"<polyline class="mismatch" points="{points}"/>"#);
+                }
+                _ => {}
+            }
+        }
+        // each line of the graph is met once per direction
+        for (u, v) in self.graph.intersections.keys().filter(|(u, v)| u < v) {
+            let line = self.graph.line(&(*u, *v));
+
+            self.line(&mut body, &mut bounds, &line, self.class(&line));
+        }
+
+        for chain in &self.graph.pruned {
+            for line in chain.windows(2) {
+                self.line(
+                    &mut body,
+                    &mut bounds,
+                    &(line[0], line[1]),
+                    &format!("pruned {}", self.class(&(line[0], line[1]))),
+                );
+            }
+        }
+
+        let planes = self.planes();
+
+        for (incident, successors) in &self.graph.intersections {
+            let (from, at) = self.graph.line(incident);
+
+            for (plane, successor) in successors {
+                let (_, to) = self.graph.line(successor);
+                let (class, colour) = match planes.iter().position(|other| other == plane) {
+                    Some(index) => ("successor", PALETTE[index % PALETTE.len()]),
+                    None => ("successor undefined", "#888888"),
+                };
+                let points = self.bend(&from, &at, &to, &mut bounds);
+
+                let _ = writeln!(
+                    body,
+                    r#"<polyline class="{class}" stroke="{colour}" points="{points}"><title>{}</title></polyline>"#,
+                    describe(plane)
+                );
+            }
+        }
+
+        let (min, max) = bounds.padded();
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n<style>\n{STYLE}\n</style>\n{body}</svg>\n",
+            min.0,
+            min.1,
+            max.0 - min.0,
+            max.1 - min.1
+        )
+    }
+
+    /// Distinct planes the successors were chosen on, in order of first appearance.
+    fn planes(&self) -> Vec<PlaneMatcher> {
+        let mut planes = Vec::<PlaneMatcher>::new();
+
+        for successors in self.graph.intersections.values() {
+            for (plane, _) in successors {
+                if !plane.is_undefined() && !planes.contains(plane) {
+                    planes.push(*plane);
+                }
+            }
+        }
+
+        planes
+    }
+
+    /// Class of `line` after its kind, whatever the direction it was digitised in.
+    fn class(&self, line: &(Coordinates, Coordinates)) -> &'static str {
+        match self
+            .kinds
+            .and_then(|kinds| kinds.get(line).or_else(|| kinds.get(&(line.1, line.0))))
+        {
+            Some(LineKind::Ridge) => "ridge",
+            Some(LineKind::Edge) => "edge",
+            Some(LineKind::RoofGap) => "roof-gap",
+            Some(LineKind::RoofGapLine) => "roof-gap-line",
+            Some(LineKind::Building) => "building",
+            Some(LineKind::Helping) => "helping",
+            None => "unknown",
+        }
+    }
+
+    fn line(
+        &self,
+        body: &mut String,
+        bounds: &mut Bounds,
+        line: &(Coordinates, Coordinates),
+        class: &str,
+    ) {
+        let (from, to) = (
+            self.projection.apply(&line.0),
+            self.projection.apply(&line.1),
+        );
+
+        bounds.extend(from);
+        bounds.extend(to);
+
+        let _ = writeln!(
+            body,
+            r#"<line class="{class}" x1="{}" y1="{}" x2="{}" y2="{}"><title>{} → {}</title></line>"#,
+            from.0,
+            from.1,
+            to.0,
+            to.1,
+            describe_coordinates(&line.0),
+            describe_coordinates(&line.1)
+        );
+    }
+
+    /// Points of the bend drawn at `at` between the lines coming from `from` and going to `to`.
+    fn bend(
+        &self,
+        from: &Coordinates,
+        at: &Coordinates,
+        to: &Coordinates,
+        bounds: &mut Bounds,
+    ) -> String {
+        [
+            along(at, from, SUCCESSOR_LENGTH),
+            *at,
+            along(at, to, SUCCESSOR_LENGTH),
+        ]
+        .iter()
+        .map(|coordinates| self.point(coordinates, bounds))
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    /// Point of `coordinates` in the view, as listed in SVG.
+    fn point(&self, coordinates: &Coordinates, bounds: &mut Bounds) -> String {
+        let point = self.projection.apply(coordinates);
+
+        bounds.extend(point);
+
+        format!("{},{}", point.0, point.1)
+    }
+}
+
+/// Rectangle bounding the points drawn.
+struct Bounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self {
+            min: (f64::MAX, f64::MAX),
+            max: (f64::MIN, f64::MIN),
+        }
+    }
+}
+
+impl Bounds {
+    fn extend(&mut self, point: (f64, f64)) {
+        self.min = (self.min.0.min(point.0), self.min.1.min(point.1));
+        self.max = (self.max.0.max(point.0), self.max.1.max(point.1));
+    }
+
+    /// Corners of the rectangle with a margin of a twentieth of its largest side, an empty
+    /// rectangle becoming a unit square.
+    fn padded(&self) -> ((f64, f64), (f64, f64)) {
+        if self.min.0 > self.max.0 {
+            return ((0f64, 0f64), (1f64, 1f64));
+        }
+
+        let margin = ((self.max.0 - self.min.0).max(self.max.1 - self.min.1) / 20f64).max(0.5);
+
+        (
+            (self.min.0 - margin, self.min.1 - margin),
+            (self.max.0 + margin, self.max.1 + margin),
+        )
+    }
+}
+
+/// Coordinates lying `share` of the way from `from` to `to`.
+fn along(from: &Coordinates, to: &Coordinates, share: f64) -> Coordinates {
+    Coordinates {
+        x: from.x + (to.x - from.x) * share,
+        y: from.y + (to.y - from.y) * share,
+        z: from.z + (to.z - from.z) * share,
+    }
+}
+
+fn describe(plane: &PlaneMatcher) -> String {
+    match (plane.normal(), plane.offset()) {
+        (Some(normal), Some(offset)) => format!(
+            "normal ({:.3}, {:.3}, {:.3}), offset {offset:.3}",
+            normal.x, normal.y, normal.z
+        ),
+        _ => "undefined plane".to_string(),
+    }
+}
+
+fn describe_coordinates(coordinates: &Coordinates) -> String {
+    format!("({}, {}, {})", coordinates.x, coordinates.y, coordinates.z)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{gable, line};
+    use crate::pathgraph::PathGraphBuilder;
+    use crate::pipeline::{polygonalize, TOLERANCES};
+
+    #[test]
+    fn graph_is_rendered() {
+        // gable roof with a dangling helping line
+        let lines = [gable(), vec![line([5.0, 8.0, 5.0], [5.0, 10.0, 5.0])]].concat();
+        let kinds = HashMap::from([(lines[3], LineKind::Ridge), (lines[7], LineKind::Helping)]);
+        let graph = PathGraphBuilder::from(&lines, &TOLERANCES[1]).build();
+        let rings = polygonalize(&lines, &TOLERANCES);
+        let svg = SvgRenderer::from(&graph)
+            .kinds(&kinds)
+            .rings(&rings)
+            .render();
+
+        assert_eq!(2, svg.matches("<polygon").count());
+        assert_eq!(8, svg.matches("<line").count());
+        assert_eq!(1, svg.matches(r#"class="ridge""#).count());
+        assert_eq!(1, svg.matches(r#"class="pruned helping""#).count());
+        // each side of both faces is followed by another one on its plane
+        assert!(svg.matches(r#"class="successor""#).count() >= 8);
+    }
+}
@@ -0,0 +1,433 @@
+use indexmap::IndexMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+
+use super::coordinates::Coordinates;
+use super::diagnostics::Diagnostics;
+use super::io::LineKind;
+use super::path::Path;
+
+/// Parametric roof shapes that can be generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoofShape {
+    Gable,
+    Hip,
+    /// Hip roof over an L-shaped building, both wings meeting along a valley.
+    LHip,
+    /// Gable roof with a gable dormer on its front slope.
+    Dormers,
+    /// Flat roof surrounded by a raised parapet.
+    FlatWithParapet,
+    /// Steep lower slopes all around a flat top.
+    Mansard,
+}
+
+/// Dimensions of the generated roofs and defects added to their lines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyntheticOptions {
+    /// Seed of the random generator, the same seed always giving the same roof.
+    pub seed: u64,
+    /// Length of the building along the x axis, in metres.
+    pub width: f64,
+    /// Length of the building along the y axis, in metres.
+    pub depth: f64,
+    /// Height of the eaves, in metres.
+    pub height: f64,
+    /// Slope of the roof faces, in degrees.
+    pub pitch: f64,
+    /// Largest displacement of each vertex along each axis, in metres.
+    pub jitter: f64,
+    /// Number of stray lines added around the roof.
+    pub noise: usize,
+    /// Probability of each line to be left out.
+    pub missing: f64,
+    /// Probability of each line to be digitised twice.
+    pub duplicated: f64,
+    /// Number of dangling helping lines added on the roof faces.
+    pub helpers: usize,
+}
+
+/// Lines of a generated roof with their kinds and the faces they are expected to form.
+#[derive(Clone)]
+pub struct SyntheticRoof {
+    pub lines: Vec<(Coordinates, Coordinates)>,
+    pub kinds: HashMap<(Coordinates, Coordinates), LineKind>,
+    /// Ground truth faces, following the jittered vertices.
+    pub faces: Vec<Path>,
+}
+
+impl Default for SyntheticOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            width: 12f64,
+            depth: 8f64,
+            height: 6f64,
+            pitch: 35f64,
+            jitter: 0f64,
+            noise: 0,
+            missing: 0f64,
+            duplicated: 0f64,
+            helpers: 0,
+        }
+    }
+}
+
+impl RoofShape {
+    pub const ALL: [RoofShape; 6] = [
+        RoofShape::Gable,
+        RoofShape::Hip,
+        RoofShape::LHip,
+        RoofShape::Dormers,
+        RoofShape::FlatWithParapet,
+        RoofShape::Mansard,
+    ];
+
+    /// Generates the lines of a roof of this shape, with the defects given by `options`.
+    pub fn generate(&self, options: &SyntheticOptions) -> SyntheticRoof {
+        let mut random = StdRng::seed_from_u64(options.seed);
+        let faces = self.faces(options);
+        // each vertex is moved once so that lines stay connected
+        let mut vertices = IndexMap::<Coordinates, Coordinates>::new();
+
+        for vertex in faces.iter().flatten() {
+            if !vertices.contains_key(vertex) {
+                let moved = Coordinates {
+                    x: vertex.x + jitter(&mut random, options.jitter),
+                    y: vertex.y + jitter(&mut random, options.jitter),
+                    z: vertex.z + jitter(&mut random, options.jitter),
+                };
+
+                vertices.insert(*vertex, moved);
+            }
+        }
+
+        // kinds are found on the exact geometry
+        let classified = self.classify(&faces);
+        let faces = faces
+            .iter()
+            .map(|ring| {
+                ring.iter()
+                    .map(|vertex| vertices[vertex])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut lines = Vec::<(Coordinates, Coordinates)>::new();
+        let mut kinds = HashMap::<(Coordinates, Coordinates), LineKind>::new();
+
+        for ((u, v), kind) in classified {
+            let (u, v) = (vertices[&u], vertices[&v]);
+
+            if random.gen_bool(options.missing.clamp(0f64, 1f64)) {
+                continue;
+            }
+
+            lines.push((u, v));
+            kinds.insert((u, v), kind);
+
+            if random.gen_bool(options.duplicated.clamp(0f64, 1f64)) {
+                lines.push((v, u));
+                kinds.insert((v, u), kind);
+            }
+        }
+
+        for _ in 0..options.helpers {
+            // from the middle of a side of a face halfway towards its centre
+            let ring = &faces[random.gen_range(0..faces.len())];
+            let side = random.gen_range(0..ring.len());
+            let (u, v) = (ring[side], ring[(side + 1) % ring.len()]);
+            let middle = midpoint(&u, &v);
+            let centre = centroid(ring);
+            let line = (middle, midpoint(&middle, &centre));
+
+            lines.push(line);
+            kinds.insert(line, LineKind::Helping);
+        }
+
+        let top = options.height + options.depth * options.pitch.to_radians().tan();
+
+        for _ in 0..options.noise {
+            let mut point = || Coordinates {
+                x: random.gen_range(-2f64..options.width + 2f64),
+                y: random.gen_range(-2f64..options.width.max(options.depth) + 2f64),
+                z: random.gen_range(options.height..top),
+            };
+
+            lines.push((point(), point()));
+        }
+
+        SyntheticRoof {
+            lines,
+            kinds,
+            faces: faces.iter().map(|ring| Path::from(ring)).collect(),
+        }
+    }
+
+    /// Rings of the faces of the roof, counterclockwise seen from above and not closed.
+    fn faces(&self, options: &SyntheticOptions) -> Vec<Vec<Coordinates>> {
+        let (w, d, h) = (options.width, options.depth, options.height);
+        let slope = options.pitch.to_radians().tan();
+        let p = |x: f64, y: f64, z: f64| Coordinates { x, y, z };
+
+        match self {
+            RoofShape::Gable => {
+                let top = h + d / 2f64 * slope;
+
+                vec![
+                    vec![
+                        p(0., 0., h),
+                        p(w, 0., h),
+                        p(w, d / 2., top),
+                        p(0., d / 2., top),
+                    ],
+                    vec![
+                        p(0., d / 2., top),
+                        p(w, d / 2., top),
+                        p(w, d, h),
+                        p(0., d, h),
+                    ],
+                ]
+            }
+            RoofShape::Hip => {
+                let (r, top) = (d / 2., h + d / 2. * slope);
+
+                vec![
+                    vec![p(0., 0., h), p(w, 0., h), p(w - r, r, top), p(r, r, top)],
+                    vec![p(w, 0., h), p(w, d, h), p(w - r, r, top)],
+                    vec![p(w, d, h), p(0., d, h), p(r, r, top), p(w - r, r, top)],
+                    vec![p(0., d, h), p(0., 0., h), p(r, r, top)],
+                ]
+            }
+            RoofShape::LHip => {
+                // wings of the same depth along both axes, the second one as long as the first
+                let (r, top, l) = (d / 2., h + d / 2. * slope, w);
+
+                vec![
+                    vec![p(0., 0., h), p(w, 0., h), p(w - r, r, top), p(r, r, top)],
+                    vec![p(w, 0., h), p(w, d, h), p(w - r, r, top)],
+                    vec![p(w, d, h), p(d, d, h), p(r, r, top), p(w - r, r, top)],
+                    vec![p(d, d, h), p(d, l, h), p(r, l - r, top), p(r, r, top)],
+                    vec![p(d, l, h), p(0., l, h), p(r, l - r, top)],
+                    vec![p(0., l, h), p(0., 0., h), p(r, r, top), p(r, l - r, top)],
+                ]
+            }
+            RoofShape::Dormers => {
+                let top = h + d / 2. * slope;
+                // dormer in the middle third of the front slope, its ridge running back level
+                // until it meets the main roof
+                let (a, b) = (w / 3., 2. * w / 3.);
+                let m = (a + b) / 2.;
+                let ridge = h + (m - a) * slope;
+                let depth = (ridge - h) / slope;
+
+                vec![
+                    vec![
+                        p(0., 0., h),
+                        p(a, 0., h),
+                        p(m, depth, ridge),
+                        p(b, 0., h),
+                        p(w, 0., h),
+                        p(w, d / 2., top),
+                        p(0., d / 2., top),
+                    ],
+                    vec![
+                        p(0., d / 2., top),
+                        p(w, d / 2., top),
+                        p(w, d, h),
+                        p(0., d, h),
+                    ],
+                    vec![p(a, 0., h), p(m, 0., ridge), p(m, depth, ridge)],
+                    vec![p(m, 0., ridge), p(b, 0., h), p(m, depth, ridge)],
+                ]
+            }
+            RoofShape::FlatWithParapet => {
+                // parapet half a metre high and wide
+                let (t, z) = (0.5f64, h + 0.5f64);
+
+                vec![
+                    vec![
+                        p(t, t, h),
+                        p(w - t, t, h),
+                        p(w - t, d - t, h),
+                        p(t, d - t, h),
+                    ],
+                    vec![p(0., 0., z), p(w, 0., z), p(w - t, t, z), p(t, t, z)],
+                    vec![p(w, 0., z), p(w, d, z), p(w - t, d - t, z), p(w - t, t, z)],
+                    vec![p(w, d, z), p(0., d, z), p(t, d - t, z), p(w - t, d - t, z)],
+                    vec![p(0., d, z), p(0., 0., z), p(t, t, z), p(t, d - t, z)],
+                ]
+            }
+            RoofShape::Mansard => {
+                // lower slopes twice as steep as the pitch, over a quarter of the depth
+                let r = d / 4.;
+                let z = h + r * (2. * options.pitch).min(75.).to_radians().tan();
+
+                vec![
+                    vec![p(0., 0., h), p(w, 0., h), p(w - r, r, z), p(r, r, z)],
+                    vec![p(w, 0., h), p(w, d, h), p(w - r, d - r, z), p(w - r, r, z)],
+                    vec![p(w, d, h), p(0., d, h), p(r, d - r, z), p(w - r, d - r, z)],
+                    vec![p(0., d, h), p(0., 0., h), p(r, r, z), p(r, d - r, z)],
+                    vec![
+                        p(r, r, z),
+                        p(w - r, r, z),
+                        p(w - r, d - r, z),
+                        p(r, d - r, z),
+                    ],
+                ]
+            }
+        }
+    }
+
+    /// Lines bounding the `faces` with their kinds, lines of two faces being ridges and the other
+    /// ones edges, except for the lines of a parapet.
+    fn classify(&self, faces: &[Vec<Coordinates>]) -> Vec<((Coordinates, Coordinates), LineKind)> {
+        let below = |vertex: &Coordinates| {
+            faces[0]
+                .iter()
+                .any(|other| other.x == vertex.x && other.y == vertex.y)
+        };
+        let mut users =
+            IndexMap::<(Coordinates, Coordinates), ((Coordinates, Coordinates), usize)>::new();
+
+        for ring in faces {
+            for index in 0..ring.len() {
+                let (u, v) = (ring[index], ring[(index + 1) % ring.len()]);
+
+                users
+                    .entry(Diagnostics::undirected(&u, &v))
+                    .or_insert(((u, v), 0))
+                    .1 += 1;
+            }
+        }
+
+        users
+            .values()
+            .map(|(line, count)| {
+                let kind = match (self, count) {
+                    (RoofShape::FlatWithParapet, 1) if faces[0].contains(&line.0) => {
+                        LineKind::RoofGapLine
+                    }
+                    // the inner lines of the parapet stand above the flat roof
+                    (RoofShape::FlatWithParapet, 1) if below(&line.0) && below(&line.1) => {
+                        LineKind::RoofGap
+                    }
+                    // the parapet is split at its corners
+                    (RoofShape::FlatWithParapet, 2) => LineKind::Helping,
+                    (_, 1) => LineKind::Edge,
+                    _ => LineKind::Ridge,
+                };
+
+                (*line, kind)
+            })
+            .collect()
+    }
+}
+
+fn jitter(random: &mut StdRng, amplitude: f64) -> f64 {
+    if amplitude > 0f64 {
+        random.gen_range(-amplitude..=amplitude)
+    } else {
+        0f64
+    }
+}
+
+fn midpoint(u: &Coordinates, v: &Coordinates) -> Coordinates {
+    Coordinates {
+        x: (u.x + v.x) / 2f64,
+        y: (u.y + v.y) / 2f64,
+        z: (u.z + v.z) / 2f64,
+    }
+}
+
+fn centroid(ring: &[Coordinates]) -> Coordinates {
+    let count = ring.len() as f64;
+
+    Coordinates {
+        x: ring.iter().map(|vertex| vertex.x).sum::<f64>() / count,
+        y: ring.iter().map(|vertex| vertex.y).sum::<f64>() / count,
+        z: ring.iter().map(|vertex| vertex.z).sum::<f64>() / count,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline::{polygonalize, TOLERANCES};
+
+    fn identifiers<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Vec<String> {
+        let mut identifiers = paths
+            .into_iter()
+            .map(|path| path.identifier(""))
+            .collect::<Vec<_>>();
+
+        identifiers.sort();
+        identifiers
+    }
+
+    #[test]
+    fn clean_roofs_give_their_faces() {
+        for shape in RoofShape::ALL {
+            let roof = shape.generate(&SyntheticOptions::default());
+            let mut expected = identifiers(&roof.faces);
+
+            if shape == RoofShape::FlatWithParapet {
+                // the level lines inside the parapet also close over the flat roof, like the lines
+                // around a courtyard
+                let sequence = &roof.faces[0].sequence;
+                let opening = sequence[..sequence.len() - 1]
+                    .iter()
+                    .map(|coordinates| Coordinates {
+                        z: coordinates.z + 0.5,
+                        ..*coordinates
+                    })
+                    .collect::<Vec<_>>();
+
+                expected.push(Path::from(&opening).identifier(""));
+                expected.sort();
+            }
+
+            assert_eq!(
+                expected,
+                identifiers(&polygonalize(&roof.lines, &TOLERANCES)),
+                "{shape:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_roof() {
+        let options = SyntheticOptions {
+            seed: 7,
+            jitter: 0.02,
+            noise: 3,
+            missing: 0.1,
+            duplicated: 0.1,
+            helpers: 2,
+            ..Default::default()
+        };
+        let first = RoofShape::LHip.generate(&options);
+        let second = RoofShape::LHip.generate(&options);
+        let other = RoofShape::LHip.generate(&SyntheticOptions { seed: 8, ..options });
+
+        assert_eq!(first.lines, second.lines);
+        assert_ne!(first.lines, other.lines);
+        // helping lines dangle and leave the faces unchanged
+        let helped = RoofShape::Hip.generate(&SyntheticOptions {
+            helpers: 3,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            3,
+            helped
+                .lines
+                .iter()
+                .filter(|line| helped.kinds[line] == LineKind::Helping)
+                .count()
+        );
+        assert_eq!(
+            identifiers(&helped.faces),
+            identifiers(&polygonalize(&helped.lines, &TOLERANCES))
+        );
+    }
+}
@@ -0,0 +1,39 @@
+/// Sine of the largest angle between the normal of a plane and the horizontal for the plane to
+/// stand vertically, such as a wall, two degrees whatever the tolerances the plane was found
+/// with, so that steep roof faces found with loose tolerances remain roof faces.
+pub const VERTICAL: f64 = 0.034_899_496_702_500_97;
+
+/// Thresholds used while building the path graph and searching for rings, each one expressed in
+/// the unit of the quantity it bounds so that they can be tuned independently.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerances {
+    /// Largest angle, in degrees, between two lines or two plane normals still considered parallel.
+    pub angle: f64,
+    /// Largest distance, in metres, from a point to a plane for the point to lie on the plane.
+    pub distance: f64,
+    /// Largest distance, in metres, between line endpoints merged into a single vertex, snapping
+    /// being disabled when zero.
+    pub snapping: f64,
+    /// Largest deviation, in degrees, of the sum of the interior angles of a ring from that of a
+    /// simple polygon with as many vertices.
+    pub angle_sum: f64,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Self {
+            angle: 5f64,
+            distance: 0.1f64,
+            snapping: 0f64,
+            angle_sum: 5f64,
+        }
+    }
+}
+
+impl Tolerances {
+    /// Sine of the angle tolerance, bounding the norm of the cross product of unit vectors
+    /// considered parallel.
+    pub fn sine(&self) -> f64 {
+        self.angle.to_radians().sin()
+    }
+}
@@ -0,0 +1,273 @@
+use indexmap::IndexMap;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::coordinates::{Coordinates, CoordinatesVector};
+use super::diagnostics::Diagnostics;
+use super::io::LineKind;
+use super::path::Path;
+use super::polygon::Polygon;
+
+/// Face of a roof, oriented with its normal pointing upwards.
+#[derive(Clone)]
+pub struct Face {
+    pub path: Path,
+    pub normal: CoordinatesVector,
+    /// Angle between the face and the horizontal plane, in degrees.
+    pub slope: f64,
+    /// Area of the face in its own plane.
+    pub area: f64,
+}
+
+/// Line shared by two faces.
+#[derive(Debug, Clone, Copy)]
+pub struct Link {
+    /// Indices of both faces.
+    pub faces: (usize, usize),
+    pub line: (Coordinates, Coordinates),
+    pub kind: Option<LineKind>,
+    /// Angle between both faces measured below the roof, in degrees, smaller than 180 along
+    /// ridges and hips and larger along valleys.
+    pub dihedral: f64,
+}
+
+/// Adjacency of the faces of a roof along the lines they share.
+#[derive(Clone)]
+pub struct RoofTopology {
+    pub faces: Vec<Face>,
+    pub links: Vec<Link>,
+    /// Closed loops of the lines bounding a single face, the outer boundary of the roof first and
+    /// counterclockwise, followed by the boundaries of its openings clockwise.
+    pub boundaries: Vec<Vec<Coordinates>>,
+}
+
+impl Face {
+    pub fn from(path: &Path) -> Self {
+        let normal = match path.normal() {
+            Some(normal) if normal.z < 0f64 => normal.flip(),
+            Some(normal) => normal,
+            None => CoordinatesVector::normalized(0f64, 0f64, 1f64),
+        };
+
+        Self {
+            path: path.clone(),
+            normal,
+            slope: normal.z.clamp(-1f64, 1f64).acos().to_degrees(),
+            area: path.area(),
+        }
+    }
+}
+
+impl RoofTopology {
+    /// Builds the topology of the faces formed by `polygons`, the `kinds` of the input lines being
+    /// attached to the lines shared by faces.
+    pub fn from(
+        polygons: &[Polygon<'_>],
+        kinds: &HashMap<(Coordinates, Coordinates), LineKind>,
+    ) -> Self {
+        let faces = polygons
+            .iter()
+            .map(|polygon| Face::from(polygon.path))
+            .collect::<Vec<_>>();
+        // faces using each line, whatever its direction
+        let mut users = IndexMap::<(Coordinates, Coordinates), Vec<usize>>::new();
+
+        for (index, face) in faces.iter().enumerate() {
+            for (u, v) in face.path.sides() {
+                users
+                    .entry(Diagnostics::undirected(&u, &v))
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        let mut links = Vec::<Link>::new();
+        let mut boundary = Vec::<(Coordinates, Coordinates)>::new();
+
+        for (line, users) in &users {
+            if users.len() == 1 {
+                boundary.push(*line);
+            }
+
+            for (i, a) in users.iter().enumerate() {
+                for b in users.iter().skip(i + 1) {
+                    links.push(Link {
+                        faces: (*a, *b),
+                        line: *line,
+                        kind: kinds
+                            .get(line)
+                            .or_else(|| kinds.get(&(line.1, line.0)))
+                            .copied(),
+                        dihedral: RoofTopology::dihedral(&faces[*a], &faces[*b], &line.0),
+                    });
+                }
+            }
+        }
+
+        Self {
+            faces,
+            links,
+            boundaries: RoofTopology::loops(&boundary),
+        }
+    }
+
+    /// Angle between faces `a` and `b` meeting at `point`, measured below the roof.
+    fn dihedral(a: &Face, b: &Face, point: &Coordinates) -> f64 {
+        let angle = a
+            .normal
+            .dot(&b.normal)
+            .clamp(-1f64, 1f64)
+            .acos()
+            .to_degrees();
+        // the second face going down from the first one forms a ridge
+        let below = a
+            .normal
+            .dot(&CoordinatesVector::unscaled(&(*point, b.path.centroid())))
+            < 0f64;
+
+        if below {
+            180f64 - angle
+        } else {
+            180f64 + angle
+        }
+    }
+
+    /// Chains `lines` into closed loops, sorted by decreasing projected area, the first one
+    /// counterclockwise and the other ones clockwise.
+    pub(crate) fn loops(lines: &[(Coordinates, Coordinates)]) -> Vec<Vec<Coordinates>> {
+        let mut adjacencies = IndexMap::<Coordinates, Vec<Coordinates>>::new();
+
+        for (u, v) in lines {
+            adjacencies.entry(*u).or_default().push(*v);
+            adjacencies.entry(*v).or_default().push(*u);
+        }
+
+        let mut loops = Vec::<Vec<Coordinates>>::new();
+
+        while let Some((start, _)) = adjacencies.iter().find(|(_, to)| !to.is_empty()) {
+            let start = *start;
+            let mut ring = vec![start];
+            let mut current = start;
+
+            while let Some(next) = adjacencies.get_mut(&current).and_then(|to| to.pop()) {
+                if let Some(to) = adjacencies.get_mut(&next) {
+                    if let Some(position) = to.iter().position(|other| *other == current) {
+                        to.swap_remove(position);
+                    }
+                }
+
+                ring.push(next);
+                current = next;
+
+                if current == start {
+                    break;
+                }
+            }
+
+            if ring.len() > 3 && ring.first() == ring.last() {
+                loops.push(ring);
+            }
+        }
+
+        loops.sort_by(|a, b| {
+            Polygon::signed_area(b)
+                .abs()
+                .total_cmp(&Polygon::signed_area(a).abs())
+        });
+
+        for (index, ring) in loops.iter_mut().enumerate() {
+            if (index == 0) != (Polygon::signed_area(ring) > 0f64) {
+                ring.reverse();
+            }
+        }
+
+        loops
+    }
+
+    /// Outer boundary of the roof.
+    pub fn outer(&self) -> Option<&Vec<Coordinates>> {
+        self.boundaries.first()
+    }
+
+    /// Faces adjacent to `face`, with the link they share.
+    pub fn neighbors(&self, face: usize) -> impl Iterator<Item = (usize, &Link)> + '_ {
+        self.links.iter().filter_map(move |link| {
+            if link.faces.0 == face {
+                Some((link.faces.1, link))
+            } else if link.faces.1 == face {
+                Some((link.faces.0, link))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let coordinates = |ring: &[Coordinates]| {
+            ring.iter()
+                .map(|coordinates| [coordinates.x, coordinates.y, coordinates.z])
+                .collect::<Vec<_>>()
+        };
+
+        json!({
+            "faces": self.faces
+                .iter()
+                .enumerate()
+                .map(|(index, face)| json!({
+                    "index": index,
+                    "label": face.path.identifier(""),
+                    "normal": [face.normal.x, face.normal.y, face.normal.z],
+                    "slope": face.slope,
+                    "ring": coordinates(&face.path.sequence)
+                }))
+                .collect::<Vec<_>>(),
+            "links": self.links
+                .iter()
+                .map(|link| json!({
+                    "faces": [link.faces.0, link.faces.1],
+                    "line": coordinates(&[link.line.0, link.line.1]),
+                    "kind": link.kind.map(|kind| format!("{kind:?}")),
+                    "dihedral": link.dihedral
+                }))
+                .collect::<Vec<_>>(),
+            "boundaries": self.boundaries
+                .iter()
+                .map(|ring| coordinates(ring))
+                .collect::<Vec<_>>()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::gable;
+    use crate::pipeline::{polygonalize, TOLERANCES};
+
+    #[test]
+    fn gable_roof_topology() {
+        let lines = gable();
+        let kinds = HashMap::from([(lines[3], LineKind::Ridge)]);
+        let paths = polygonalize(&lines, &TOLERANCES);
+        let polygons = paths.iter().map(Polygon::from).collect::<Vec<_>>();
+        let topology = RoofTopology::from(&polygons, &kinds);
+
+        assert_eq!(2, topology.faces.len());
+        assert_eq!(1, topology.links.len());
+        assert_eq!(Some(LineKind::Ridge), topology.links[0].kind);
+        // both faces slope by atan(3 / 4)
+        let slope = (3f64 / 4f64).atan().to_degrees();
+        assert!(topology
+            .faces
+            .iter()
+            .all(|face| (face.slope - slope).abs() < 1e-9));
+        assert!((topology.links[0].dihedral - (180f64 - 2f64 * slope)).abs() < 1e-9);
+        // the outer boundary goes around the six other lines
+        assert_eq!(1, topology.boundaries.len());
+        assert_eq!(7, topology.outer().unwrap().len());
+        assert!(Polygon::signed_area(topology.outer().unwrap()) > 0f64);
+        assert_eq!(1, topology.neighbors(0).count());
+        assert_eq!(1, topology.to_json()["links"].as_array().unwrap().len());
+    }
+}
@@ -0,0 +1,227 @@
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+use super::coordinates::Coordinates;
+
+/// Decision taken while searching a path graph for rings, given by the coordinates involved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// The search did not follow `line` on to `successor` again on a plane it already did.
+    CacheHit {
+        line: (Coordinates, Coordinates),
+        successor: Coordinates,
+    },
+    /// The successor of `line` was not followed, its plane not matching the plane of the path.
+    PlaneMismatch {
+        line: (Coordinates, Coordinates),
+        successor: (Coordinates, Coordinates),
+    },
+    /// The search walked back from `line`, which reached a vertex of the path other than its start.
+    Backtrack { line: (Coordinates, Coordinates) },
+    /// The ring closed by the search was rejected as not a valid polygon on its plane.
+    Rejected { ring: Vec<Coordinates> },
+    /// The ring closed by the search was kept.
+    Closure { ring: Vec<Coordinates> },
+}
+
+/// Receiver of the decisions taken while searching for rings, as set on
+/// [`PathBuilder::trace`](super::path::PathBuilder::trace).
+pub trait TraceSink {
+    fn record(&mut self, event: &TraceEvent);
+}
+
+/// Writes each event as a json object on its own line, optionally keeping only the events
+/// involving given vertices.
+pub struct JsonLinesTrace<W: Write> {
+    writer: W,
+    vertices: Option<HashSet<Coordinates>>,
+}
+
+impl TraceEvent {
+    /// Coordinates involved in the event.
+    pub fn vertices(&self) -> Vec<Coordinates> {
+        match self {
+            TraceEvent::CacheHit { line, successor } => vec![line.0, line.1, *successor],
+            TraceEvent::PlaneMismatch { line, successor } => {
+                vec![line.0, line.1, successor.0, successor.1]
+            }
+            TraceEvent::Backtrack { line } => vec![line.0, line.1],
+            TraceEvent::Rejected { ring } | TraceEvent::Closure { ring } => ring.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            TraceEvent::CacheHit { line, successor } => json!({
+                "event": "cache_hit",
+                "line": [position(&line.0), position(&line.1)],
+                "successor": position(successor),
+            }),
+            TraceEvent::PlaneMismatch { line, successor } => json!({
+                "event": "plane_mismatch",
+                "line": [position(&line.0), position(&line.1)],
+                "successor": [position(&successor.0), position(&successor.1)],
+            }),
+            TraceEvent::Backtrack { line } => json!({
+                "event": "backtrack",
+                "line": [position(&line.0), position(&line.1)],
+            }),
+            TraceEvent::Rejected { ring } => json!({
+                "event": "rejected",
+                "ring": ring.iter().map(position).collect::<Vec<_>>(),
+            }),
+            TraceEvent::Closure { ring } => json!({
+                "event": "closure",
+                "ring": ring.iter().map(position).collect::<Vec<_>>(),
+            }),
+        }
+    }
+
+    /// Reads back the events written to `reader`, such as the file of a [`JsonLinesTrace`],
+    /// skipping the lines that are not events.
+    pub fn replay(reader: impl BufRead) -> io::Result<Vec<TraceEvent>> {
+        let mut events = Vec::<TraceEvent>::new();
+
+        for line in reader.lines() {
+            if let Some(event) = serde_json::from_str::<Value>(&line?)
+                .ok()
+                .as_ref()
+                .and_then(TraceEvent::from_json)
+            {
+                events.push(event);
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Reads back an event written by [`TraceEvent::to_json`].
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let line = |value: &Value| Some((coordinates(&value[0])?, coordinates(&value[1])?));
+        let ring = |value: &Value| {
+            value
+                .as_array()?
+                .iter()
+                .map(coordinates)
+                .collect::<Option<Vec<_>>>()
+        };
+
+        match value["event"].as_str()? {
+            "cache_hit" => Some(TraceEvent::CacheHit {
+                line: line(&value["line"])?,
+                successor: coordinates(&value["successor"])?,
+            }),
+            "plane_mismatch" => Some(TraceEvent::PlaneMismatch {
+                line: line(&value["line"])?,
+                successor: line(&value["successor"])?,
+            }),
+            "backtrack" => Some(TraceEvent::Backtrack {
+                line: line(&value["line"])?,
+            }),
+            "rejected" => Some(TraceEvent::Rejected {
+                ring: ring(&value["ring"])?,
+            }),
+            "closure" => Some(TraceEvent::Closure {
+                ring: ring(&value["ring"])?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl TraceSink for Vec<TraceEvent> {
+    fn record(&mut self, event: &TraceEvent) {
+        self.push(event.clone());
+    }
+}
+
+impl<W: Write> JsonLinesTrace<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            vertices: None,
+        }
+    }
+
+    /// Keeps only the events involving any of the `vertices`.
+    pub fn filter(mut self, vertices: &[Coordinates]) -> Self {
+        self.vertices = Some(vertices.iter().copied().collect());
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> TraceSink for JsonLinesTrace<W> {
+    fn record(&mut self, event: &TraceEvent) {
+        let involved = match &self.vertices {
+            Some(vertices) => event
+                .vertices()
+                .iter()
+                .any(|vertex| vertices.contains(vertex)),
+            None => true,
+        };
+
+        if involved {
+            // tracing is a debugging aid which must not abort the search
+            let _ = writeln!(self.writer, "{}", event.to_json());
+        }
+    }
+}
+
+fn position(coordinates: &Coordinates) -> [f64; 3] {
+    [coordinates.x, coordinates.y, coordinates.z]
+}
+
+fn coordinates(value: &Value) -> Option<Coordinates> {
+    Some(Coordinates {
+        x: value[0].as_f64()?,
+        y: value[1].as_f64()?,
+        z: value[2].as_f64()?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::gable;
+    use crate::path::PathBuilder;
+    use crate::pathgraph::PathGraphBuilder;
+    use crate::pipeline::TOLERANCES;
+
+    #[test]
+    fn search_is_traced() {
+        let lines = gable();
+        let graph = PathGraphBuilder::from(&lines, &TOLERANCES[1]).build();
+        let mut events = Vec::<TraceEvent>::new();
+        let paths = PathBuilder::from(&graph).trace(&mut events).build();
+
+        assert_eq!(2, paths.len());
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, TraceEvent::Closure { .. })));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, TraceEvent::PlaneMismatch { .. })));
+        // only the events at the eave corner are written, and read back as they were
+        let corner = lines[0].0;
+        let mut trace = JsonLinesTrace::new(Vec::<u8>::new()).filter(&[corner]);
+
+        PathBuilder::from(&graph).trace(&mut trace).build();
+
+        let replayed = TraceEvent::replay(trace.into_inner().as_slice()).unwrap();
+        let expected = events
+            .into_iter()
+            .filter(|event| event.vertices().contains(&corner))
+            .collect::<Vec<_>>();
+
+        assert!(!replayed.is_empty());
+        assert_eq!(expected, replayed);
+    }
+}
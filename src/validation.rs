@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use super::coordinates::Coordinates;
+use super::diagnostics::Diagnostics;
+use super::io::LineKind;
+use super::path::Path;
+use super::tolerances::Tolerances;
+
+/// Number of faces using a line as one of their sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coverage {
+    /// Used by no face.
+    Unused,
+    /// Used by a single face, as expected from boundary lines.
+    Single,
+    /// Used by two faces, as expected from interior lines.
+    Double,
+    /// Used by more than two faces, never expected from a roof surface.
+    Multiple(usize),
+}
+
+/// Coverage of an input line compared to what its kind leads to expect.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeReport {
+    pub line: (Coordinates, Coordinates),
+    pub kind: Option<LineKind>,
+    pub coverage: Coverage,
+    /// Whether the coverage is consistent with the kind of the line.
+    pub expected: bool,
+}
+
+/// Classification of every input line of a building by the number of output faces using it.
+#[derive(Debug, Clone)]
+pub struct Validation {
+    pub edges: Vec<EdgeReport>,
+    /// Share of the lines whose coverage is expected, one for a clean roof surface.
+    pub score: f64,
+}
+
+impl Coverage {
+    pub fn from(count: usize) -> Self {
+        match count {
+            0 => Coverage::Unused,
+            1 => Coverage::Single,
+            2 => Coverage::Double,
+            count => Coverage::Multiple(count),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        match self {
+            Coverage::Unused => 0,
+            Coverage::Single => 1,
+            Coverage::Double => 2,
+            Coverage::Multiple(count) => *count,
+        }
+    }
+}
+
+impl LineKind {
+    /// Number of faces expected to use a line of this kind.
+    ///
+    /// Ridges separate two roof faces and edges bound a single one. Steps and helping lines either
+    /// separate faces or bound the roof. Building outlines lie on the ground and only bound walls.
+    pub fn expected_coverage(&self) -> RangeInclusive<usize> {
+        match self {
+            LineKind::Ridge => 2..=2,
+            LineKind::Edge => 1..=1,
+            LineKind::RoofGap | LineKind::RoofGapLine | LineKind::Helping => 1..=2,
+            LineKind::Building => 0..=1,
+        }
+    }
+}
+
+impl Validation {
+    /// Validates the faces formed by `paths` with the `tolerances` against the input `lines` and
+    /// their `kinds`, lines of unknown kind being expected to bound one or two faces. Lines are
+    /// related to the sides of the paths as in [`Diagnostics::cover`], and those collapsing to a
+    /// single point when snapped are expected to bound none.
+    pub fn of(
+        lines: &[(Coordinates, Coordinates)],
+        kinds: &HashMap<(Coordinates, Coordinates), LineKind>,
+        paths: &[Path],
+        tolerances: &[Tolerances],
+    ) -> Self {
+        let coverage = Diagnostics::coverage(paths);
+        let edges = lines
+            .iter()
+            .zip(Diagnostics::candidates(lines, tolerances))
+            .map(|(line, candidates)| {
+                let kind = kinds.get(line).copied();
+                let count = candidates
+                    .iter()
+                    .filter_map(|line| coverage.get(line))
+                    .max()
+                    .copied()
+                    .unwrap_or(0);
+                let expected = if candidates.iter().any(|(a, b)| a == b) {
+                    0..=0
+                } else {
+                    kind.map_or(1..=2, |kind| kind.expected_coverage())
+                };
+
+                EdgeReport {
+                    line: *line,
+                    kind,
+                    coverage: Coverage::from(count),
+                    expected: expected.contains(&count),
+                }
+            })
+            .collect::<Vec<_>>();
+        let score = if edges.is_empty() {
+            1f64
+        } else {
+            edges.iter().filter(|edge| edge.expected).count() as f64 / edges.len() as f64
+        };
+
+        Self { edges, score }
+    }
+
+    /// Lines whose coverage is suspicious given their kind.
+    pub fn issues(&self) -> impl Iterator<Item = &EdgeReport> + '_ {
+        self.edges.iter().filter(|edge| !edge.expected)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{gable, line};
+    use crate::pipeline::{polygonalize, TOLERANCES};
+
+    #[test]
+    fn gable_roof_is_clean() {
+        // two faces meeting along the ridge
+        let lines = gable();
+        let mut kinds = lines
+            .iter()
+            .map(|line| (*line, LineKind::Edge))
+            .collect::<HashMap<_, _>>();
+        kinds.insert(lines[3], LineKind::Ridge);
+
+        let paths = polygonalize(&lines, &TOLERANCES);
+        let validation = Validation::of(&lines, &kinds, &paths, &TOLERANCES);
+
+        assert_eq!(2, paths.len());
+        assert_eq!(0, validation.issues().count());
+        assert_eq!(1f64, validation.score);
+        assert_eq!(Coverage::Double, validation.edges[3].coverage);
+
+        // a ridge bounding a single face is suspicious
+        let validation = Validation::of(&lines, &kinds, &paths[..1], &TOLERANCES);
+        let issues = validation.issues().collect::<Vec<_>>();
+
+        assert!(issues
+            .iter()
+            .any(|edge| edge.kind == Some(LineKind::Ridge) && edge.coverage == Coverage::Single));
+        assert!(validation.score < 1f64);
+    }
+
+    #[test]
+    fn snapped_lines_are_used() {
+        // a square whose last line was digitised slightly off its first corner
+        let lines = vec![
+            line([0.0, 0.0, 0.0], [10.0, 0.0, 0.0]),
+            line([10.0, 0.0, 0.0], [10.0, 10.0, 0.0]),
+            line([10.0, 10.0, 0.0], [0.0, 10.0, 0.0]),
+            line([0.0, 10.0, 0.0], [0.02, 0.01, 0.0]),
+        ];
+        let kinds = lines
+            .iter()
+            .map(|line| (*line, LineKind::Edge))
+            .collect::<HashMap<_, _>>();
+        let tolerances = [Tolerances {
+            snapping: 0.05,
+            ..Tolerances::default()
+        }];
+
+        let paths = polygonalize(&lines, &tolerances);
+        let validation = Validation::of(&lines, &kinds, &paths, &tolerances);
+
+        assert_eq!(1, paths.len());
+        assert_eq!(Coverage::Single, validation.edges[3].coverage);
+        assert_eq!(0, validation.issues().count());
+        assert_eq!(1f64, validation.score);
+    }
+}